@@ -0,0 +1,92 @@
+use inc_lib::core::config::Condition;
+use inc_lib::exec::executor::{execute_command_line, RunOptions, RunningProcesses};
+use inc_lib::libs::secrets::Secrets;
+use inc_lib::libs::shell::build_command_line;
+use std::collections::HashMap;
+use std::env;
+use std::path::PathBuf;
+
+/**
+ * Where a condition is checked. Relative paths, and `command_succeeds`, use the directory the
+ * step would run in.
+ */
+pub struct ConditionContext<'a> {
+    pub working_dir: Option<PathBuf>,
+    pub shell: Option<&'a str>,
+    pub env: &'a HashMap<String, String>,
+    pub processes: &'a RunningProcesses,
+    pub secrets: &'a Secrets,
+}
+
+pub fn evaluate(condition: &Condition, context: &ConditionContext) -> bool {
+    return match condition {
+        Condition::Os(os) => {
+            let os = if os == "darwin" { "macos" } else { os.as_str() };
+            os == env::consts::OS || os == env::consts::FAMILY
+        }
+        Condition::Env(expected) => {
+            let (name, value) = match expected.find('=') {
+                Some(position) => (&expected[..position], Some(&expected[position + 1..])),
+                None => (expected.as_str(), None),
+            };
+            let actual = context
+                .env
+                .get(name)
+                .cloned()
+                .or_else(|| env::var(name).ok());
+            match (actual, value) {
+                (Some(actual), Some(value)) => actual == value,
+                (Some(actual), None) => !actual.is_empty(),
+                (None, _) => false,
+            }
+        }
+        Condition::FileExists(path) => {
+            let path = PathBuf::from(path);
+            match context.working_dir {
+                Some(ref dir) if path.is_relative() => dir.join(path).exists(),
+                _ => path.exists(),
+            }
+        }
+        Condition::CommandSucceeds(command) => command_succeeds(command, context),
+        Condition::All(conditions) => conditions.iter().all(|x| evaluate(x, context)),
+        Condition::Any(conditions) => conditions.iter().any(|x| evaluate(x, context)),
+        Condition::Not(condition) => !evaluate(condition, context),
+    };
+}
+
+fn command_succeeds(command: &str, context: &ConditionContext) -> bool {
+    let command_line = match build_command_line(context.shell, command) {
+        Ok(value) => value,
+        Err(err) => {
+            warn!("Unable to check `{}`: {}", command, err);
+            return false;
+        }
+    };
+
+    let options = RunOptions {
+        working_dir: context.working_dir.clone(),
+        prefix: None,
+        quiet: true,
+        timeout: None,
+        record_output: false,
+        log_file: None,
+        secrets: context.secrets.clone(),
+    };
+    let result = execute_command_line(
+        &command_line,
+        context.env.clone(),
+        &options,
+        context.processes,
+    );
+
+    return match result {
+        Ok(code) => {
+            debug!("`{}` returned {}", command, code);
+            code == 0
+        }
+        Err(err) => {
+            debug!("Unable to run `{}`: {}", command, err.message);
+            false
+        }
+    };
+}
@@ -0,0 +1,82 @@
+#[cfg(test)]
+pub mod test {
+    use exec::condition::*;
+    use inc_lib::core::config::Condition;
+    use inc_lib::exec::executor::RunningProcesses;
+    use inc_lib::libs::secrets::Secrets;
+    use std::collections::HashMap;
+    use std::env;
+
+    fn check(condition: Condition, env: &HashMap<String, String>) -> bool {
+        let processes = RunningProcesses::new();
+        let secrets = Secrets::default();
+        return evaluate(
+            &condition,
+            &ConditionContext {
+                working_dir: Some(env::temp_dir()),
+                shell: None,
+                env: env,
+                processes: &processes,
+                secrets: &secrets,
+            },
+        );
+    }
+
+    #[test]
+    fn test_os() {
+        let env = HashMap::new();
+        assert!(check(Condition::Os(s!(env::consts::OS)), &env));
+        assert!(check(Condition::Os(s!(env::consts::FAMILY)), &env));
+        assert!(!check(Condition::Os(s!("plan9")), &env));
+    }
+
+    #[test]
+    fn test_env() {
+        let mut env = HashMap::new();
+        env.insert(s!("INC_TEST_TARGET"), s!("staging"));
+        env.insert(s!("INC_TEST_EMPTY"), s!(""));
+
+        assert!(check(Condition::Env(s!("INC_TEST_TARGET")), &env));
+        assert!(check(Condition::Env(s!("INC_TEST_TARGET=staging")), &env));
+        assert!(!check(Condition::Env(s!("INC_TEST_TARGET=production")), &env));
+        assert!(!check(Condition::Env(s!("INC_TEST_EMPTY")), &env));
+        assert!(check(Condition::Env(s!("INC_TEST_EMPTY=")), &env));
+        assert!(!check(Condition::Env(s!("INC_TEST_NOT_SET")), &env));
+    }
+
+    #[test]
+    fn test_file_exists() {
+        let env = HashMap::new();
+        let dir = env::temp_dir();
+        let name = format!("inc-condition-test-{}", ::std::process::id());
+        ::std::fs::write(dir.join(&name), "").unwrap();
+
+        assert!(check(Condition::FileExists(name.clone()), &env));
+        assert!(check(Condition::FileExists(s!(dir.join(&name).to_string_lossy())), &env));
+        ::std::fs::remove_file(dir.join(&name)).unwrap();
+        assert!(!check(Condition::FileExists(name), &env));
+    }
+
+    #[test]
+    fn test_command_succeeds() {
+        let mut env = HashMap::new();
+        env.insert(s!("INC_TEST_CODE"), s!("3"));
+        assert!(check(Condition::CommandSucceeds(s!("true")), &env));
+        assert!(!check(Condition::CommandSucceeds(s!("exit $INC_TEST_CODE")), &env));
+    }
+
+    #[test]
+    fn test_combinations() {
+        let env = HashMap::new();
+        let yes = || Condition::Os(s!(env::consts::OS));
+        let no = || Condition::Os(s!("plan9"));
+
+        assert!(check(Condition::All(vec![yes(), yes()]), &env));
+        assert!(!check(Condition::All(vec![yes(), no()]), &env));
+        assert!(check(Condition::All(vec![]), &env));
+        assert!(check(Condition::Any(vec![no(), yes()]), &env));
+        assert!(!check(Condition::Any(vec![no(), no()]), &env));
+        assert!(check(Condition::Not(Box::new(no())), &env));
+        assert!(!check(Condition::Not(Box::new(yes())), &env));
+    }
+}
@@ -0,0 +1,148 @@
+use glob::glob;
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const STATE_DIR: &'static str = ".inc/fingerprints";
+
+/**
+ * Tracks the `inputs` and `outputs` of a command, so it can be skipped when none of them, nor
+ * the command itself, changed since it last succeeded. The state is kept in
+ * `.inc/fingerprints`, next to the `inc.yaml` that defined the command.
+ */
+pub struct Fingerprint {
+    project_dir: PathBuf,
+    state_file: PathBuf,
+    inputs: Vec<String>,
+    outputs: Vec<String>,
+    definition: String,
+}
+
+impl Fingerprint {
+    pub fn new(
+        project_dir: &Path,
+        name: &str,
+        inputs: &[String],
+        outputs: &[String],
+        definition: String,
+    ) -> Self {
+        return Fingerprint {
+            project_dir: project_dir.to_path_buf(),
+            state_file: project_dir.join(STATE_DIR).join(state_file_name(name)),
+            inputs: inputs.to_vec(),
+            outputs: outputs.to_vec(),
+            definition: definition,
+        };
+    }
+
+    pub fn is_up_to_date(&self) -> bool {
+        let saved = match fs::read_to_string(&self.state_file) {
+            Ok(value) => value,
+            Err(_) => return false,
+        };
+
+        return match self.compute() {
+            Ok(Some(current)) => current == saved,
+            Ok(None) => false,
+            Err(err) => {
+                warn!("Unable to check if the command is up to date: {}", err);
+                false
+            }
+        };
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        let current = match self.compute() {
+            Ok(Some(value)) => value,
+            Ok(None) => return self.clear(),
+            Err(err) => return Err(err),
+        };
+
+        if let Some(dir) = self.state_file.parent() {
+            if let Err(err) = fs::create_dir_all(dir) {
+                return Err(format!("Unable to create {:?}: {}", dir, err));
+            }
+        }
+
+        return match fs::write(&self.state_file, current) {
+            Ok(_) => Ok(()),
+            Err(err) => Err(format!("Unable to write {:?}: {}", self.state_file, err)),
+        };
+    }
+
+    fn clear(&self) -> Result<(), String> {
+        if self.state_file.exists() {
+            if let Err(err) = fs::remove_file(&self.state_file) {
+                return Err(format!("Unable to remove {:?}: {}", self.state_file, err));
+            }
+        }
+
+        return Ok(());
+    }
+
+    /**
+     * One line for the command, then one for every file. `None` when an output is missing,
+     * which means the command always has to run.
+     */
+    fn compute(&self) -> Result<Option<String>, String> {
+        let mut state = format!("definition {}\n", hash(self.definition.as_bytes()));
+
+        for (kind, patterns) in [("input", &self.inputs), ("output", &self.outputs)].iter() {
+            for pattern in patterns.iter() {
+                let files = match self.find_files(pattern) {
+                    Ok(value) => value,
+                    Err(err) => return Err(err),
+                };
+
+                if files.is_empty() && *kind == "output" {
+                    debug!("No outputs match {}", pattern);
+                    return Ok(None);
+                }
+
+                for file in files.iter() {
+                    let contents = match fs::read(self.project_dir.join(file)) {
+                        Ok(value) => value,
+                        Err(err) => return Err(format!("Unable to read {}: {}", file, err)),
+                    };
+                    state.push_str(&format!("{} {} {}\n", kind, hash(&contents), file));
+                }
+            }
+        }
+
+        return Ok(Some(state));
+    }
+
+    /**
+     * Files matching `pattern`, relative to the project and sorted, so the state is stable.
+     */
+    fn find_files(&self, pattern: &str) -> Result<Vec<String>, String> {
+        let full_pattern = self.project_dir.join(pattern);
+        let paths = match glob(&full_pattern.to_string_lossy()) {
+            Ok(value) => value,
+            Err(err) => return Err(format!("Invalid glob {}: {}", pattern, err)),
+        };
+
+        let mut files: Vec<String> = paths
+            .filter_map(|x| x.ok())
+            .filter(|x| x.is_file())
+            .map(|x| {
+                let relative = x.strip_prefix(&self.project_dir).unwrap_or(&x).to_path_buf();
+                s!(relative.to_string_lossy())
+            }).collect();
+        files.sort();
+
+        return Ok(files);
+    }
+}
+
+fn state_file_name(name: &str) -> String {
+    let name: String = name
+        .chars()
+        .map(|x| if x.is_alphanumeric() || x == '-' || x == '_' { x } else { '_' })
+        .collect();
+    return format!("{}.txt", name);
+}
+
+fn hash(contents: &[u8]) -> String {
+    return format!("{:x}", Sha256::digest(contents));
+}
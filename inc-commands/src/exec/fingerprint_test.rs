@@ -0,0 +1,69 @@
+#[cfg(test)]
+pub mod test {
+    use exec::fingerprint::*;
+    use std::fs;
+    use std::path::PathBuf;
+
+    fn project_dir(name: &str) -> PathBuf {
+        let dir = ::std::env::temp_dir().join(format!(
+            "inc-fingerprint-test-{}-{}",
+            name,
+            ::std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("src")).unwrap();
+        fs::write(dir.join("src/main.rs"), "fn main() {}").unwrap();
+        return dir;
+    }
+
+    #[test]
+    fn test_inputs_and_definition() {
+        let dir = project_dir("inputs");
+        let fingerprint = |definition: &str| {
+            Fingerprint::new(&dir, "build", &[s!("src/*.rs")], &[], s!(definition))
+        };
+
+        assert!(!fingerprint("cargo build").is_up_to_date());
+        fingerprint("cargo build").save().unwrap();
+        assert!(fingerprint("cargo build").is_up_to_date());
+        assert!(dir.join(".inc/fingerprints/build.txt").exists());
+
+        assert!(!fingerprint("cargo build --release").is_up_to_date());
+
+        fs::write(dir.join("src/lib.rs"), "pub fn lib() {}").unwrap();
+        assert!(!fingerprint("cargo build").is_up_to_date());
+        fingerprint("cargo build").save().unwrap();
+        assert!(fingerprint("cargo build").is_up_to_date());
+
+        fs::write(dir.join("src/lib.rs"), "pub fn changed() {}").unwrap();
+        assert!(!fingerprint("cargo build").is_up_to_date());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_missing_outputs() {
+        let dir = project_dir("outputs");
+        let fingerprint = Fingerprint::new(
+            &dir,
+            "dist[target=linux]",
+            &[s!("src/*.rs")],
+            &[s!("dist/*")],
+            s!("make dist"),
+        );
+
+        fingerprint.save().unwrap();
+        assert!(!fingerprint.is_up_to_date());
+
+        fs::create_dir_all(dir.join("dist")).unwrap();
+        fs::write(dir.join("dist/app"), "binary").unwrap();
+        fingerprint.save().unwrap();
+        assert!(fingerprint.is_up_to_date());
+        assert!(dir.join(".inc/fingerprints/dist_target_linux_.txt").exists());
+
+        fs::remove_file(dir.join("dist/app")).unwrap();
+        assert!(!fingerprint.is_up_to_date());
+        fingerprint.save().unwrap();
+        assert!(!dir.join(".inc/fingerprints/dist_target_linux_.txt").exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
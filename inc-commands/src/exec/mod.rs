@@ -0,0 +1,821 @@
+use clap::{App, AppSettings, Arg, ArgMatches, SubCommand};
+use inc_lib::core::command::AvaliableCommands;
+use inc_lib::core::config::{
+    CommandAndEnv, Commands, ConfigContainer, EnvValue, ExecCommandConfig, ExecConfig, MatrixConfig,
+    ParamConfig, ServiceConfig,
+};
+use inc_lib::exec::executor::{CliError, CliResult, RunningProcesses};
+use inc_lib::libs::interpolate::interpolate;
+use inc_lib::libs::output::{serialize, OutputFormat};
+use inc_lib::libs::secrets::MASK;
+use inc_lib::libs::shell::join_args;
+use std::collections::{BTreeMap, HashMap};
+use std::env::{self, current_dir};
+use std::fmt::Write;
+use std::path::PathBuf;
+use std::thread::available_parallelism;
+
+mod condition;
+mod fingerprint;
+mod params;
+mod report;
+mod scheduler;
+mod services;
+mod watch;
+
+#[cfg(test)]
+pub(crate) mod condition_test;
+#[cfg(test)]
+pub(crate) mod fingerprint_test;
+#[cfg(test)]
+pub(crate) mod params_test;
+#[cfg(test)]
+pub(crate) mod report_test;
+#[cfg(test)]
+pub(crate) mod scheduler_test;
+#[cfg(test)]
+pub(crate) mod services_test;
+#[cfg(test)]
+pub(crate) mod watch_test;
+
+use self::params::{describe_param, params_help, parse_params};
+use self::report::{ReportFormat, ReportOptions};
+use self::scheduler::{run_commands, ExecOptions, ScheduledCommand};
+use self::watch::{watch, WatchOptions};
+use list::format_arg;
+
+const ARGS_PLACEHOLDER: &'static str = "{{args}}";
+
+pub fn subcommand<'a, 'b>() -> App<'a, 'b> {
+    return SubCommand::with_name("exec")
+        .about("Execute commands from the project.")
+        .setting(AppSettings::ArgRequiredElseHelp)
+        .arg(
+            Arg::with_name("list-commands")
+                .long("list-commands")
+                .help("List all of the avaliable commands."),
+        ).arg(format_arg().requires("list-commands"))
+        .arg(
+            Arg::with_name("jobs")
+                .long("jobs")
+                .short("j")
+                .help("Number of steps to run at the same time.")
+                .takes_value(true),
+        ).arg(
+            Arg::with_name("keep-going")
+                .long("keep-going")
+                .help("Keep running the steps that don't depend on a failed step."),
+        ).arg(
+            Arg::with_name("force")
+                .long("force")
+                .help("Run commands even when their inputs and outputs haven't changed."),
+        ).arg(
+            Arg::with_name("dry-run")
+                .long("dry-run")
+                .help("Show what would run, without running anything."),
+        ).arg(
+            Arg::with_name("watch")
+                .long("watch")
+                .help("Run the command again every time a watched file changes."),
+        ).arg(
+            Arg::with_name("clear")
+                .long("clear")
+                .requires("watch")
+                .help("Clear the screen before every run, when watching."),
+        ).arg(
+            Arg::with_name("report")
+                .long("report")
+                .help("Write what ran, and how long it took, to a file.")
+                .takes_value(true)
+                .value_name("PATH"),
+        ).arg(
+            Arg::with_name("report-format")
+                .long("report-format")
+                .help("Format of the report, JUnit XML when the path ends in .xml, otherwise JSON.")
+                .takes_value(true)
+                .possible_values(&["json", "junit"])
+                .requires("report"),
+        ).arg(
+            Arg::with_name("report-output")
+                .long("report-output")
+                .requires("report")
+                .help("Include the output of every step in the report."),
+        ).arg(
+            Arg::with_name("log-dir")
+                .long("log-dir")
+                .value_name("DIR")
+                .takes_value(true)
+                .help("Also write the output of every step to a file in DIR. Defaults to the log_dir from inc.yaml."),
+        ).arg(
+            Arg::with_name("env")
+                .long("env")
+                .help("Set an environment variable for every step, as KEY=VALUE.")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1),
+        ).arg(
+            Arg::with_name("command")
+                .help("Names of the commands to execute, in order.")
+                .takes_value(true)
+                .multiple(true)
+                .required(true)
+                .required_unless("list-commands"),
+        ).arg(
+            Arg::with_name("args")
+                .help("Extra arguments passed to the last command, after `--`.")
+                .multiple(true)
+                .last(true),
+        );
+}
+
+pub fn execute(
+    args: &ArgMatches,
+    _commands: AvaliableCommands,
+    config: ConfigContainer,
+) -> CliResult {
+    let exec_configs = match config.get_exec_configs() {
+        Ok(value) => value,
+        Err(err) => return Err(CliError::new(2, err)),
+    };
+
+    if args.is_present("list-commands") {
+        let format = OutputFormat::from_name(args.value_of("format"));
+        if format == OutputFormat::Text {
+            info!("{}", generate_list_options(&exec_configs));
+            return Ok(0);
+        }
+
+        return match serialize(&list_commands(&exec_configs), &format) {
+            Ok(output) => {
+                println!("{}", output);
+                Ok(0)
+            }
+            Err(err) => Err(CliError::new(1, err)),
+        };
+    }
+
+    let commands_to_exec: Vec<&str> = args.values_of("command").unwrap().collect();
+    debug!("Going to exec {}", commands_to_exec.join(", "));
+
+    if commands_to_exec[0] == "help" && !exec_configs.commands.contains_key("help") {
+        return show_help(&exec_configs, &commands_to_exec[1..]);
+    }
+
+    let execution_order = match exec_configs.resolve_dependencies(&commands_to_exec) {
+        Ok(value) => value,
+        Err(err) => return Err(CliError::new(2, err)),
+    };
+    debug!("Execution order: {:?}", execution_order);
+
+    let extra_args: Vec<String> = match args.values_of("args") {
+        Some(v) => v.map(|x| s!(x)).collect(),
+        None => Vec::new(),
+    };
+
+    let command_with_args = *commands_to_exec.last().unwrap();
+    let mut params: HashMap<String, HashMap<String, String>> = HashMap::new();
+    for command_name in execution_order.iter() {
+        let config = exec_configs.commands.get(command_name).unwrap();
+        if config.params.is_empty() {
+            continue;
+        }
+
+        let result = if command_name == command_with_args {
+            parse_params(command_name, config, &extra_args)
+        } else {
+            parse_params(command_name, config, &[]).map_err(|err| {
+                CliError::new(
+                    err.code,
+                    format!(
+                        "Only the last command gets params, so {} uses it's defaults.\n{}",
+                        command_name, err.message
+                    ),
+                )
+            })
+        };
+        match result {
+            Ok(Some(values)) => params.insert(command_name.clone(), values),
+            Ok(None) => return Ok(0),
+            Err(err) => return Err(err),
+        };
+    }
+    let extra_args = if params.contains_key(command_with_args) {
+        Vec::new()
+    } else {
+        extra_args
+    };
+
+    let mut cli_env: HashMap<String, String> = HashMap::new();
+    for value in args.values_of("env").into_iter().flat_map(|x| x) {
+        match value.find('=') {
+            Some(position) if position > 0 => {
+                cli_env.insert(s!(value[..position]), s!(value[position + 1..]));
+            }
+            _ => {
+                return Err(CliError::new(
+                    2,
+                    format!("--env must be KEY=VALUE, not {}", value),
+                ));
+            }
+        }
+    }
+
+    let jobs = match args.value_of("jobs") {
+        Some(value) => match value.parse::<usize>() {
+            Ok(jobs) if jobs > 0 => jobs,
+            _ => {
+                return Err(CliError::new(
+                    2,
+                    format!("Jobs must be a positive number, not {}", value),
+                ));
+            }
+        },
+        None => {
+            if execution_order
+                .iter()
+                .any(|x| exec_configs.commands.get(x).unwrap().parallel)
+            {
+                available_parallelism().map(|x| x.get()).unwrap_or(1)
+            } else {
+                1
+            }
+        }
+    };
+
+    let options = ExecOptions {
+        jobs: jobs,
+        keep_going: args.is_present("keep-going"),
+        force: args.is_present("force"),
+        report: args.value_of("report").map(|path| {
+            let path = PathBuf::from(path);
+            ReportOptions {
+                format: ReportFormat::from_name(args.value_of("report-format"), &path),
+                path: path,
+                include_output: args.is_present("report-output"),
+            }
+        }),
+        log_dir: args
+            .value_of("log-dir")
+            .map(PathBuf::from)
+            .or(exec_configs.log_dir.clone()),
+    };
+    let build_plan = || {
+        build_plan(
+            &exec_configs,
+            &execution_order,
+            &commands_to_exec,
+            command_with_args,
+            &extra_args,
+            &params,
+            &cli_env,
+        )
+    };
+
+    if args.is_present("dry-run") {
+        let plan = match build_plan() {
+            Ok(value) => value,
+            Err(err) => return Err(err),
+        };
+        let descriptions: Vec<String> = plan
+            .iter()
+            .enumerate()
+            .map(|(i, command)| command.describe(i + 1))
+            .collect();
+        info!("{}", descriptions.join("\n").trim_end());
+        return Ok(0);
+    }
+
+    if args.is_present("watch") {
+        let project_dir = exec_configs
+            .command_defintions
+            .get(commands_to_exec[0])
+            .and_then(|x| x.parent())
+            .map(|x| x.to_path_buf())
+            .or_else(|| current_dir().ok())
+            .unwrap();
+        let patterns: Vec<String> = commands_to_exec
+            .iter()
+            .flat_map(|x| exec_configs.commands.get(*x).unwrap().watch.iter().cloned())
+            .collect();
+        let watch_options = WatchOptions {
+            project_dir: project_dir,
+            patterns: patterns,
+            clear: args.is_present("clear"),
+        };
+        return watch(watch_options, options, build_plan);
+    }
+
+    return match build_plan() {
+        Ok(plan) => run_commands(plan, options, RunningProcesses::new()),
+        Err(err) => Err(err),
+    };
+}
+
+/**
+ * Shows the params of each command, for `inc exec help <command>`.
+ */
+fn show_help(config: &ExecConfig, names: &[&str]) -> CliResult {
+    if names.is_empty() {
+        info!("{}", generate_list_options(config));
+        return Ok(0);
+    }
+
+    for name in names.iter() {
+        let command = match config.commands.get(*name) {
+            Some(value) => value,
+            None => {
+                return Err(CliError::new(
+                    2,
+                    format!("Unable to find command list for {}! Failing!", name),
+                ))
+            }
+        };
+        match params_help(name, command) {
+            Ok(help) => info!("{}", help),
+            Err(err) => return Err(CliError::new(2, err)),
+        }
+    }
+
+    return Ok(0);
+}
+
+/**
+ * Everything that will run, in the order from `resolve_dependencies`. Only `command_with_args`
+ * gets the extra args. Each of the `named` commands, and the dependencies it added, run after
+ * the command named before it.
+ */
+fn build_plan(
+    exec_configs: &ExecConfig,
+    execution_order: &[String],
+    named: &[&str],
+    command_with_args: &str,
+    extra_args: &[String],
+    params: &HashMap<String, HashMap<String, String>>,
+    cli_env: &HashMap<String, String>,
+) -> Result<Vec<ScheduledCommand>, CliError> {
+    let mut scheduled_commands: Vec<ScheduledCommand> = Vec::new();
+    // Where the commands added for each one in `execution_order` start, and what they run after
+    let mut stages: Vec<(usize, Option<&str>)> = Vec::new();
+    let mut previous_named: Option<&str> = None;
+    for command_name in execution_order.iter() {
+        stages.push((scheduled_commands.len(), previous_named));
+        if named.contains(&command_name.as_str()) {
+            previous_named = Some(command_name);
+        }
+
+        let config = exec_configs.commands.get(command_name).unwrap();
+        let command_args = if command_name == command_with_args {
+            extra_args.to_vec()
+        } else {
+            Vec::new()
+        };
+
+        let defined_in = exec_configs.command_defintions.get(command_name).cloned();
+        let mut fixed_env = params.get(command_name).cloned().unwrap_or_default();
+        fixed_env.extend(cli_env.clone());
+
+        let matrix = match config.matrix {
+            Some(ref matrix) => matrix,
+            None => {
+                scheduled_commands.push(schedule_command(
+                    command_name,
+                    command_name,
+                    config,
+                    &defined_in,
+                    &BTreeMap::new(),
+                    &fixed_env,
+                    &command_args,
+                )?);
+                continue;
+            }
+        };
+
+        let combinations = match matrix.combinations() {
+            Ok(ref value) if value.is_empty() => {
+                return Err(CliError::new(
+                    2,
+                    format!(
+                        "The matrix of {} doesn't have any combinations! Failing!",
+                        command_name
+                    ),
+                ))
+            }
+            Ok(value) => value,
+            Err(err) => {
+                return Err(CliError::new(
+                    2,
+                    format!("Unable to use the matrix of {}: {}! Failing!", command_name, err),
+                ))
+            }
+        };
+
+        let mut combination_config = config.clone();
+        combination_config.parallel = false;
+        combination_config.matrix = None;
+        let mut combination_names: Vec<String> = Vec::new();
+        for combination in combinations.iter() {
+            let values: Vec<String> = combination
+                .iter()
+                .map(|(name, value)| format!("{}={}", name, value))
+                .collect();
+            let name = format!("{}[{}]", command_name, values.join(","));
+
+            let mut combination_env = fixed_env.clone();
+            for (key, value) in combination.iter() {
+                combination_env.insert(format!("INC_MATRIX_{}", key.to_uppercase()), value.clone());
+            }
+
+            let mut scheduled_command = schedule_command(
+                &name,
+                command_name,
+                &combination_config,
+                &defined_in,
+                combination,
+                &combination_env,
+                &command_args,
+            )?;
+            if !config.parallel {
+                scheduled_command.set_serial_group(command_name);
+            }
+            scheduled_commands.push(scheduled_command);
+            combination_names.push(name);
+        }
+
+        // Commands that depend on this one wait for all of the combinations.
+        let mut combined_config = config.clone();
+        combined_config.commands = Vec::new();
+        combined_config.finally = Vec::new();
+        combined_config.services = Vec::new();
+        combined_config.depends_on = combination_names;
+        combined_config.when = None;
+        combined_config.inputs = Vec::new();
+        combined_config.outputs = Vec::new();
+        combined_config.matrix = None;
+        match ScheduledCommand::new(
+            command_name,
+            &combined_config,
+            Vec::new(),
+            Vec::new(),
+            defined_in,
+            s!(""),
+        ) {
+            Ok(value) => scheduled_commands.push(value),
+            Err(err) => return Err(CliError::new(2, err)),
+        }
+    }
+
+    for (i, &(start, previous)) in stages.iter().enumerate() {
+        let end = stages.get(i + 1).map_or(scheduled_commands.len(), |x| x.0);
+        if let Some(previous) = previous {
+            for scheduled_command in scheduled_commands[start..end].iter_mut() {
+                scheduled_command.set_runs_after(previous);
+            }
+        }
+    }
+
+    return Ok(scheduled_commands);
+}
+
+/**
+ * Builds the steps of `config`, with `name` as the name of the command that's scheduled, and
+ * `command_name` as the name it has in `inc.yaml`.
+ */
+fn schedule_command(
+    name: &str,
+    command_name: &str,
+    config: &ExecCommandConfig,
+    defined_in: &Option<PathBuf>,
+    matrix: &BTreeMap<String, String>,
+    fixed_env: &HashMap<String, String>,
+    command_args: &[String],
+) -> Result<ScheduledCommand, CliError> {
+    let (config, commands, finally) =
+        match build_steps(command_name, config, defined_in, matrix, fixed_env) {
+            Ok(value) => value,
+            Err(err) => return Err(CliError::new(2, err)),
+        };
+    let commands = apply_extra_args(commands, command_args);
+
+    return ScheduledCommand::new(
+        name,
+        &config,
+        commands,
+        finally,
+        defined_in.clone(),
+        join_args(command_args),
+    ).map_err(|err| CliError::new(2, err));
+}
+
+/**
+ * Gives each step it's full environment, with the step's `env` over the command's and
+ * `fixed_env`, the params and `--env`, over both. Then expands `${VAR}` in the steps, before
+ * anything runs, so a missing `${VAR:?}` fails the whole plan. Env values are expanded first, so
+ * the rest of the step can refer to them. Values from `fixed_env` are used as-is, and `matrix`
+ * has the values for `${matrix.NAME}`. The `finally` steps are built the same way, and returned
+ * separately. Services get the same environment as steps, and are expanded the same way.
+ */
+fn build_steps(
+    name: &str,
+    config: &ExecCommandConfig,
+    defined_in: &Option<PathBuf>,
+    matrix: &BTreeMap<String, String>,
+    fixed_env: &HashMap<String, String>,
+) -> Result<(ExecCommandConfig, Vec<CommandAndEnv>, Vec<CommandAndEnv>), String> {
+    let builtin = |variable: &str| -> Option<String> {
+        return match variable {
+            "inc.command" => Some(s!(name)),
+            "inc.config_file" => defined_in.as_ref().map(|x| s!(x.to_string_lossy())),
+            "inc.project_dir" => defined_in
+                .as_ref()
+                .and_then(|x| x.parent())
+                .map(|x| s!(x.to_string_lossy())),
+            _ if variable.starts_with("inc.") => None,
+            _ if variable.starts_with("matrix.") => matrix.get(&variable[7..]).cloned(),
+            _ if fixed_env.contains_key(variable) => fixed_env.get(variable).cloned(),
+            _ => env::var(variable).ok(),
+        };
+    };
+    let with_context = |err: String| format!("Unable to expand variables in {}: {}", name, err);
+
+    let mut config = config.clone();
+    config
+        .env
+        .extend(fixed_env.iter().map(|(key, value)| (key.clone(), EnvValue::from(value.clone()))));
+    if let Some(cwd) = config.cwd.clone() {
+        config.cwd = Some(interpolate(&cwd, &builtin).map_err(with_context)?);
+    }
+
+    let expand_env = |env: &HashMap<String, EnvValue>| -> Result<HashMap<String, String>, String> {
+        let mut expanded: HashMap<String, String> = HashMap::new();
+        for (key, value) in config.env.iter().chain(env.iter()) {
+            if !fixed_env.contains_key(key) {
+                let value = interpolate(value.value(), &builtin).map_err(with_context)?;
+                expanded.insert(key.clone(), value);
+            }
+        }
+        expanded.extend(fixed_env.clone());
+        return Ok(expanded);
+    };
+    let to_env = |env: HashMap<String, String>| -> HashMap<String, EnvValue> {
+        return env.into_iter().map(|(key, value)| (key, EnvValue::from(value))).collect();
+    };
+
+    let build_step = |step: &Commands| -> Result<CommandAndEnv, String> {
+        let mut step = step.clone().to_command_and_envs();
+        let command_env = expand_env(&step.command_env)?;
+
+        let lookup = |variable: &str| -> Option<String> {
+            return match command_env.get(variable) {
+                Some(value) => Some(value.clone()),
+                None => builtin(variable),
+            };
+        };
+        step.command = interpolate(&step.command, &lookup).map_err(with_context)?;
+        let mut argv: Vec<String> = Vec::new();
+        for arg in step.argv.iter() {
+            argv.push(interpolate(arg, &lookup).map_err(with_context)?);
+        }
+        step.argv = argv;
+        if let Some(cwd) = step.cwd.clone() {
+            step.cwd = Some(interpolate(&cwd, &lookup).map_err(with_context)?);
+        }
+        if let Some(ref mut wait_for) = step.wait_for {
+            let targets = vec![
+                &mut wait_for.tcp,
+                &mut wait_for.file,
+                &mut wait_for.http,
+                &mut wait_for.command,
+            ];
+            for target in targets {
+                if let Some(value) = target.clone() {
+                    *target = Some(interpolate(&value, &lookup).map_err(with_context)?);
+                }
+            }
+        }
+
+        step.command_env = to_env(command_env);
+        return Ok(step);
+    };
+
+    let mut commands: Vec<CommandAndEnv> = Vec::new();
+    for step in config.commands.iter() {
+        commands.push(build_step(step)?);
+    }
+    let mut finally: Vec<CommandAndEnv> = Vec::new();
+    for step in config.finally.iter() {
+        finally.push(build_step(step)?);
+    }
+
+    let mut services: Vec<ServiceConfig> = Vec::new();
+    for service in config.services.iter() {
+        let mut service = service.clone();
+        let env = expand_env(&service.env)?;
+        let lookup = |variable: &str| -> Option<String> {
+            return match env.get(variable) {
+                Some(value) => Some(value.clone()),
+                None => builtin(variable),
+            };
+        };
+        service.command = interpolate(&service.command, &lookup).map_err(with_context)?;
+        if let Some(cwd) = service.cwd.clone() {
+            service.cwd = Some(interpolate(&cwd, &lookup).map_err(with_context)?);
+        }
+        service.env = to_env(env);
+        services.push(service);
+    }
+    config.services = services;
+
+    return Ok((config, commands, finally));
+}
+
+/**
+ * When any step uses the `{{args}}` placeholder the extra args are substituted there,
+ * otherwise they are appended to the last step. Steps using `argv`, and scripts, get the args
+ * as separate arguments, instead of quoted into the command.
+ */
+fn apply_extra_args(commands: Vec<CommandAndEnv>, extra_args: &[String]) -> Vec<CommandAndEnv> {
+    let has_placeholder = commands.iter().any(|x| {
+        x.command.contains(ARGS_PLACEHOLDER)
+            || x.argv.iter().any(|x| x == ARGS_PLACEHOLDER)
+            || x.script.as_ref().map_or(false, |x| x.contains(ARGS_PLACEHOLDER))
+    });
+    let last_command = commands.len().saturating_sub(1);
+    let quoted_args = join_args(extra_args);
+
+    return commands
+        .into_iter()
+        .enumerate()
+        .map(|(i, mut entry)| {
+            if has_placeholder {
+                entry.command = entry.command.replace(ARGS_PLACEHOLDER, &quoted_args);
+                entry.script = entry
+                    .script
+                    .map(|x| x.replace(ARGS_PLACEHOLDER, &quoted_args));
+                let mut argv: Vec<String> = Vec::new();
+                for arg in entry.argv.into_iter() {
+                    if arg == ARGS_PLACEHOLDER {
+                        argv.extend(extra_args.iter().cloned());
+                    } else {
+                        argv.push(arg);
+                    }
+                }
+                entry.argv = argv;
+            } else if i == last_command && !extra_args.is_empty() {
+                if entry.argv.is_empty() && entry.script.is_none() {
+                    entry.command = format!("{} {}", entry.command, quoted_args);
+                } else {
+                    entry.argv.extend(extra_args.iter().cloned());
+                }
+            }
+            entry
+        }).collect();
+}
+
+#[derive(Serialize)]
+struct CommandList {
+    commands: Vec<CommandListing>,
+}
+
+#[derive(Serialize)]
+struct CommandListing {
+    name: String,
+    description: String,
+    defined_in: Option<String>,
+    depends_on: Vec<String>,
+    env: BTreeMap<String, String>,
+    params: Vec<ParamConfig>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    matrix: Option<MatrixConfig>,
+    steps: Vec<StepListing>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    finally: Vec<StepListing>,
+}
+
+#[derive(Serialize)]
+struct StepListing {
+    #[serde(rename = "type")]
+    kind: &'static str,
+    command: String,
+    env: BTreeMap<String, String>,
+}
+
+/**
+ * The commands for `--format json` and `--format yaml`, sorted by name. Scripts have the whole
+ * script as the `command`, and `wait_for` steps what they wait for.
+ */
+fn list_commands(config: &ExecConfig) -> CommandList {
+    let mut names: Vec<&String> = config.commands.keys().collect();
+    names.sort();
+
+    let commands = names
+        .into_iter()
+        .map(|name| {
+            let value = config.commands.get(name).unwrap().clone();
+            let secrets = value.secrets.clone();
+            let list_steps = |steps: Vec<Commands>| -> Vec<StepListing> {
+                return steps
+                    .into_iter()
+                    .map(|x| x.to_command_and_envs())
+                    .map(|step| {
+                        let (kind, command) = match step.script {
+                            Some(ref script) => ("script", script.clone()),
+                            None if step.wait_for.is_some() => {
+                                ("wait_for", step.wait_for.as_ref().unwrap().display())
+                            }
+                            None if !step.argv.is_empty() => ("argv", join_args(&step.argv)),
+                            None => ("command", step.command.clone()),
+                        };
+                        StepListing {
+                            kind: kind,
+                            command: command,
+                            env: hide_secrets(step.command_env, &secrets),
+                        }
+                    }).collect();
+            };
+
+            CommandListing {
+                name: name.clone(),
+                description: value.description,
+                defined_in: config
+                    .command_defintions
+                    .get(name)
+                    .map(|x| s!(x.to_string_lossy())),
+                depends_on: value.depends_on,
+                env: hide_secrets(value.env, &secrets),
+                params: value.params,
+                matrix: value.matrix,
+                steps: list_steps(value.commands),
+                finally: list_steps(value.finally),
+            }
+        }).collect();
+
+    return CommandList { commands: commands };
+}
+
+fn list_steps(list: &mut String, steps: Vec<Commands>, secrets: &[String]) {
+    for command in steps.into_iter().map(|x| x.to_command_and_envs()) {
+        write!(list, "     - command: {}\n", command.display_command()).unwrap();
+        write!(
+            list,
+            "       env: {:?}\n",
+            hide_secrets(command.command_env, secrets)
+        ).unwrap();
+    }
+}
+
+fn hide_secrets(env: HashMap<String, EnvValue>, secrets: &[String]) -> BTreeMap<String, String> {
+    return env
+        .into_iter()
+        .map(|(key, value)| {
+            if secrets.contains(&key) {
+                (key, s!(MASK))
+            } else {
+                (key, s!(value.value()))
+            }
+        }).collect();
+}
+
+fn generate_list_options(config: &ExecConfig) -> String {
+    let mut list = String::new();
+    write!(&mut list, "Avaliable Commands:\n").unwrap();
+
+    let command_map = config.commands.clone();
+    let mut commands: Vec<&String> = command_map.keys().collect();
+    commands.sort();
+
+    for key in commands.iter() {
+        let value = command_map.get(*key).unwrap().clone();
+        write!(&mut list, " - name: {}\n", key).unwrap();
+        write!(&mut list, "   description: {}\n", value.description).unwrap();
+        if !value.depends_on.is_empty() {
+            write!(
+                &mut list,
+                "   depends_on: {}\n",
+                value.depends_on.join(", ")
+            ).unwrap();
+        }
+        if !value.params.is_empty() {
+            write!(&mut list, "   params:\n").unwrap();
+            for param in value.params.iter() {
+                write!(&mut list, "     - {}\n", describe_param(param)).unwrap();
+            }
+        }
+        if let Some(Ok(combinations)) = value.matrix.as_ref().map(|x| x.combinations()) {
+            write!(&mut list, "   matrix:\n").unwrap();
+            for combination in combinations.iter() {
+                let values: Vec<String> = combination
+                    .iter()
+                    .map(|(name, value)| format!("{}={}", name, value))
+                    .collect();
+                write!(&mut list, "     - {}\n", values.join(", ")).unwrap();
+            }
+        }
+        write!(&mut list, "   commands:\n").unwrap();
+        list_steps(&mut list, value.commands, &value.secrets);
+        if !value.finally.is_empty() {
+            write!(&mut list, "   finally:\n").unwrap();
+            list_steps(&mut list, value.finally, &value.secrets);
+        }
+    }
+    return list;
+}
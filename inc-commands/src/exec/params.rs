@@ -0,0 +1,206 @@
+use clap::{App, AppSettings, Arg, ArgMatches, ErrorKind};
+use inc_lib::core::config::{ExecCommandConfig, ParamConfig, ParamType};
+use inc_lib::exec::executor::CliError;
+use std::collections::HashMap;
+use std::env::current_dir;
+
+/**
+ * The environment variable a param is given to the steps in, like `INC_PARAM_DRY_RUN` for
+ * `dry-run`.
+ */
+pub fn param_env_name(param: &ParamConfig) -> String {
+    return format!(
+        "INC_PARAM_{}",
+        param.name.to_uppercase().replace('-', "_")
+    );
+}
+
+/**
+ * Parses the args after `--` as the command's params, using the defaults for anything that
+ * wasn't given. Returns `None` when `--help` was asked for, after showing the help.
+ */
+pub fn parse_params(
+    name: &str,
+    config: &ExecCommandConfig,
+    args: &[String],
+) -> Result<Option<HashMap<String, String>>, CliError> {
+    let result = with_param_app(name, config, |app| {
+        return app
+            .get_matches_from_safe(args)
+            .map(|matches| param_values(config, &matches));
+    });
+
+    return match result {
+        Ok(Ok(values)) => Ok(Some(values)),
+        Ok(Err(ref err)) if err.kind == ErrorKind::HelpDisplayed => {
+            info!("{}", err.message);
+            Ok(None)
+        }
+        Ok(Err(err)) => Err(CliError::new(2, err.message)),
+        Err(err) => Err(CliError::new(2, err)),
+    };
+}
+
+fn param_values(config: &ExecCommandConfig, matches: &ArgMatches) -> HashMap<String, String> {
+    let mut values: HashMap<String, String> = HashMap::new();
+    for param in config.params.iter() {
+        let value = match param.kind {
+            ParamType::Bool if matches.is_present(&param.name) => s!("true"),
+            ParamType::Bool => param
+                .default
+                .as_ref()
+                .map(|x| x.to_string())
+                .unwrap_or(s!("false")),
+            ParamType::Path => match matches.value_of(&param.name) {
+                Some(value) => s!(current_dir().unwrap().join(value).to_string_lossy()),
+                None => continue,
+            },
+            _ => match matches.value_of(&param.name) {
+                Some(value) => s!(value),
+                None => continue,
+            },
+        };
+        values.insert(param_env_name(param), value);
+    }
+
+    return values;
+}
+
+/**
+ * The help for `inc exec help <command>`.
+ */
+pub fn params_help(name: &str, config: &ExecCommandConfig) -> Result<String, String> {
+    return with_param_app(name, config, |app| {
+        let mut help: Vec<u8> = Vec::new();
+        app.write_help(&mut help).unwrap();
+        s!(String::from_utf8_lossy(&help))
+    });
+}
+
+/**
+ * How a param is used, like `--env <staging|production> (default: staging)`, for listing the
+ * commands.
+ */
+pub fn describe_param(param: &ParamConfig) -> String {
+    let mut description = match param.kind {
+        ParamType::Bool => format!("--{}", param.name),
+        ParamType::Enum => format!("--{} <{}>", param.name, param.values.join("|")),
+        ParamType::Int => format!("--{} <int>", param.name),
+        ParamType::Path => format!("--{} <path>", param.name),
+        ParamType::String => format!("--{} <string>", param.name),
+    };
+
+    match param.default {
+        Some(ref default) => description.push_str(&format!(" (default: {})", default)),
+        None if param.required => description.push_str(" (required)"),
+        None => {}
+    }
+    if let Some(ref help) = param.help {
+        description.push_str(&format!(" {}", help));
+    }
+
+    return description;
+}
+
+/**
+ * Builds the clap app for the command's params, and hands it to `action`. The defaults are
+ * checked here, because clap doesn't check them.
+ */
+fn with_param_app<T, F>(name: &str, config: &ExecCommandConfig, action: F) -> Result<T, String>
+where
+    F: FnOnce(App) -> T,
+{
+    let mut defaults: Vec<Option<String>> = Vec::new();
+    for param in config.params.iter() {
+        if let Err(err) = check_param(param) {
+            return Err(format!(
+                "The param `{}` of {} is invalid, {}! Failing!",
+                param.name, name, err
+            ));
+        }
+        defaults.push(param.default.as_ref().map(|x| x.to_string()));
+    }
+    let after_help = match config.params.first() {
+        Some(param) => format!(
+            "The steps get the params as environment variables, like {}.",
+            param_env_name(param)
+        ),
+        None => s!("This command doesn't have any params."),
+    };
+
+    let mut app = App::new(name)
+        .bin_name(format!("inc exec {} --", name))
+        .about(config.description.as_str())
+        .after_help(after_help.as_str())
+        .template("{about}\n\nUSAGE:\n    {usage}\n\n{all-args}\n\n{after-help}")
+        .setting(AppSettings::NoBinaryName)
+        .setting(AppSettings::ColorNever)
+        .setting(AppSettings::DisableVersion);
+
+    for (param, default) in config.params.iter().zip(defaults.iter()) {
+        let mut arg = Arg::with_name(&param.name).long(&param.name);
+        if let Some(ref help) = param.help {
+            arg = arg.help(help);
+        }
+
+        if param.kind != ParamType::Bool {
+            arg = arg.takes_value(true);
+            arg = match default {
+                Some(ref default) => arg.default_value(default),
+                None => arg.required(param.required),
+            };
+        }
+
+        arg = match param.kind {
+            ParamType::Enum => arg.possible_values(
+                &param.values.iter().map(|x| x.as_str()).collect::<Vec<&str>>(),
+            ),
+            ParamType::Int => arg.validator(|value| match value.parse::<i64>() {
+                Ok(_) => Ok(()),
+                Err(_) => Err(format!("`{}` isn't a whole number", value)),
+            }),
+            _ => arg,
+        };
+
+        app = app.arg(arg);
+    }
+
+    return Ok(action(app));
+}
+
+fn check_param(param: &ParamConfig) -> Result<(), String> {
+    let valid_name = param
+        .name
+        .chars()
+        .all(|x| x.is_ascii_alphanumeric() || x == '-' || x == '_');
+    if param.name.is_empty() || param.name.starts_with('-') || !valid_name {
+        return Err(s!("names can only have letters, numbers, `-` and `_`"));
+    }
+    if param.name == "help" {
+        return Err(s!("`help` is used to show the params"));
+    }
+
+    let default = param.default.as_ref().map(|x| x.to_string());
+    return match param.kind {
+        ParamType::Enum if param.values.is_empty() => Err(s!("an enum needs `values`")),
+        ParamType::Enum => match default {
+            Some(ref value) if !param.values.contains(value) => {
+                Err(format!("the default `{}` isn't one of the values", value))
+            }
+            _ => Ok(()),
+        },
+        ParamType::Int => match default {
+            Some(ref value) if value.parse::<i64>().is_err() => {
+                Err(format!("the default `{}` isn't a whole number", value))
+            }
+            _ => Ok(()),
+        },
+        ParamType::Bool => match default {
+            Some(ref value) if value != "true" && value != "false" => {
+                Err(format!("the default `{}` isn't true or false", value))
+            }
+            _ => Ok(()),
+        },
+        _ => Ok(()),
+    };
+}
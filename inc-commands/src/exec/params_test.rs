@@ -0,0 +1,134 @@
+#[cfg(test)]
+pub mod test {
+    use exec::params::*;
+    use inc_lib::core::config::ExecCommandConfig;
+    use serde_yaml;
+    use std::collections::HashMap;
+    use std::env::current_dir;
+
+    fn deploy() -> ExecCommandConfig {
+        return serde_yaml::from_str::<ExecCommandConfig>(
+            "description: Deploys the app
+params:
+    - name: env
+      type: enum
+      values: [staging, production]
+      default: staging
+      help: Where to deploy
+    - name: replicas
+      type: int
+      default: 2
+    - name: dry-run
+      type: bool
+    - name: tag
+      required: true
+    - name: config
+      type: path
+commands:
+    - ./deploy.sh",
+        ).unwrap();
+    }
+
+    fn args(values: &[&str]) -> Vec<String> {
+        return values.iter().map(|x| s!(*x)).collect();
+    }
+
+    fn parse(values: &[&str]) -> HashMap<String, String> {
+        return match parse_params("deploy", &deploy(), &args(values)) {
+            Ok(Some(values)) => values,
+            Ok(None) => panic!("Only the help was shown"),
+            Err(err) => panic!("{}", err.message),
+        };
+    }
+
+    #[test]
+    fn test_parse_params() {
+        let values = parse(&["--tag", "v1"]);
+        assert_eq!(values.get("INC_PARAM_ENV"), Some(&s!("staging")));
+        assert_eq!(values.get("INC_PARAM_REPLICAS"), Some(&s!("2")));
+        assert_eq!(values.get("INC_PARAM_DRY_RUN"), Some(&s!("false")));
+        assert_eq!(values.get("INC_PARAM_TAG"), Some(&s!("v1")));
+        assert_eq!(values.get("INC_PARAM_CONFIG"), None);
+
+        let values = parse(&[
+            "--tag", "v2", "--env", "production", "--replicas", "5", "--dry-run", "--config",
+            "deploy.toml",
+        ]);
+        assert_eq!(values.get("INC_PARAM_ENV"), Some(&s!("production")));
+        assert_eq!(values.get("INC_PARAM_REPLICAS"), Some(&s!("5")));
+        assert_eq!(values.get("INC_PARAM_DRY_RUN"), Some(&s!("true")));
+        assert_eq!(
+            values.get("INC_PARAM_CONFIG"),
+            Some(&s!(current_dir().unwrap().join("deploy.toml").to_string_lossy()))
+        );
+    }
+
+    #[test]
+    fn test_invalid_values() {
+        let error_code = |values: &[&str]| match parse_params("deploy", &deploy(), &args(values)) {
+            Ok(_) => None,
+            Err(err) => Some(err.code),
+        };
+        assert_eq!(error_code(&[]), Some(2));
+        assert_eq!(error_code(&["--tag", "v1", "--env", "qa"]), Some(2));
+        assert_eq!(error_code(&["--tag", "v1", "--replicas", "many"]), Some(2));
+        assert_eq!(error_code(&["--tag", "v1", "--unknown"]), Some(2));
+        assert_eq!(error_code(&["--help"]), None);
+    }
+
+    #[test]
+    fn test_invalid_params() {
+        let check = |params: &str| {
+            let config = serde_yaml::from_str::<ExecCommandConfig>(&format!(
+                "params:\n{}\ncommands: [make]",
+                params
+            )).unwrap();
+            return match parse_params("broken", &config, &[]) {
+                Ok(_) => s!(""),
+                Err(err) => err.message,
+            };
+        };
+
+        assert!(check("    - name: --env").contains("names can only have letters"));
+        assert!(check("    - name: help").contains("`help` is used to show the params"));
+        assert!(check("    - name: env\n      type: enum").contains("an enum needs `values`"));
+        assert!(
+            check("    - name: env\n      type: enum\n      values: [a]\n      default: b")
+                .contains("the default `b` isn't one of the values")
+        );
+        assert!(
+            check("    - name: count\n      type: int\n      default: many")
+                .contains("the default `many` isn't a whole number")
+        );
+        assert!(
+            check("    - name: fast\n      type: bool\n      default: yes")
+                .contains("the default `yes` isn't true or false")
+        );
+    }
+
+    #[test]
+    fn test_describe_param() {
+        let config = deploy();
+        let described: Vec<String> = config.params.iter().map(|x| describe_param(x)).collect();
+        assert_eq!(
+            described,
+            vec![
+                s!("--env <staging|production> (default: staging) Where to deploy"),
+                s!("--replicas <int> (default: 2)"),
+                s!("--dry-run"),
+                s!("--tag <string> (required)"),
+                s!("--config <path>"),
+            ]
+        );
+        assert_eq!(param_env_name(&config.params[2]), "INC_PARAM_DRY_RUN");
+    }
+
+    #[test]
+    fn test_params_help() {
+        let help = params_help("deploy", &deploy()).unwrap();
+        assert!(help.starts_with("Deploys the app\n\nUSAGE:\n    inc exec deploy --"), "{}", help);
+        assert!(help.contains("--env <env>"));
+        assert!(help.contains("Where to deploy"));
+        assert!(help.contains("The steps get the params as environment variables, like INC_PARAM_ENV."));
+    }
+}
@@ -0,0 +1,206 @@
+use inc_lib::libs::output::{serialize, OutputFormat};
+use std::fmt::Write;
+use std::fs;
+use std::path::PathBuf;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ReportFormat {
+    Json,
+    Junit,
+}
+
+impl ReportFormat {
+    /**
+     * Without a format, paths ending in `.xml` get JUnit XML, and everything else JSON.
+     */
+    pub fn from_name(name: Option<&str>, path: &PathBuf) -> Self {
+        return match name {
+            Some("junit") => ReportFormat::Junit,
+            Some(_) => ReportFormat::Json,
+            None if path.extension().map_or(false, |x| x == "xml") => ReportFormat::Junit,
+            None => ReportFormat::Json,
+        };
+    }
+}
+
+/**
+ * Where `--report` writes, and what goes in it.
+ */
+#[derive(Debug, Clone)]
+pub struct ReportOptions {
+    pub path: PathBuf,
+    pub format: ReportFormat,
+    pub include_output: bool,
+}
+
+#[derive(Serialize)]
+pub struct Report {
+    pub started_at: String,
+    pub finished_at: String,
+    pub duration_secs: f64,
+    pub exit_code: i32,
+    pub commands: Vec<CommandReport>,
+}
+
+/**
+ * `status` is one of `succeeded`, `failed`, `skipped` (`when` didn't match), `up_to_date`,
+ * `stopped` (it was running when the run stopped), or `not_run`.
+ */
+#[derive(Serialize)]
+pub struct CommandReport {
+    pub name: String,
+    pub defined_in: Option<String>,
+    pub status: &'static str,
+    pub started_at: Option<String>,
+    pub finished_at: Option<String>,
+    pub duration_secs: Option<f64>,
+    pub steps: Vec<StepReport>,
+}
+
+/**
+ * `status` is one of `succeeded`, `failed`, `skipped` or `not_run`. The steps of a command
+ * that was skipped, or up to date, are `skipped`. Only steps that ran have times.
+ */
+#[derive(Serialize)]
+pub struct StepReport {
+    pub command: String,
+    pub status: &'static str,
+    pub started_at: Option<String>,
+    pub finished_at: Option<String>,
+    pub duration_secs: Option<f64>,
+    pub exit_code: Option<i32>,
+    pub attempts: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub output: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub log_file: Option<String>,
+}
+
+pub fn write_report(report: &Report, options: &ReportOptions) -> Result<(), String> {
+    let contents = match options.format {
+        ReportFormat::Json => serialize(report, &OutputFormat::Json)?,
+        ReportFormat::Junit => to_junit(report),
+    };
+
+    if let Some(parent) = options.path.parent() {
+        if !parent.as_os_str().is_empty() {
+            fs::create_dir_all(parent).map_err(|x| x.to_string())?;
+        }
+    }
+
+    return fs::write(&options.path, contents).map_err(|x| x.to_string());
+}
+
+/**
+ * Every command is a `testsuite`, with a `testcase` for each of it's steps. Steps that were
+ * skipped, or never ran, are `skipped`.
+ */
+fn to_junit(report: &Report) -> String {
+    let count = |status: &str| {
+        report
+            .commands
+            .iter()
+            .flat_map(|x| x.steps.iter())
+            .filter(|x| x.status == status)
+            .count()
+    };
+    let steps: usize = report.commands.iter().map(|x| x.steps.len()).sum();
+
+    let mut xml = String::new();
+    write!(&mut xml, "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n").unwrap();
+    write!(
+        &mut xml,
+        "<testsuites name=\"inc exec\" tests=\"{}\" failures=\"{}\" skipped=\"{}\" time=\"{:.3}\">\n",
+        steps,
+        count("failed"),
+        count("skipped") + count("not_run"),
+        report.duration_secs
+    ).unwrap();
+
+    for command in report.commands.iter() {
+        let failures = command.steps.iter().filter(|x| x.status == "failed").count();
+        let skipped = command
+            .steps
+            .iter()
+            .filter(|x| x.status == "skipped" || x.status == "not_run")
+            .count();
+        write!(
+            &mut xml,
+            "  <testsuite name=\"{}\" tests=\"{}\" failures=\"{}\" skipped=\"{}\" time=\"{:.3}\"",
+            escape_xml(&command.name),
+            command.steps.len(),
+            failures,
+            skipped,
+            command.duration_secs.unwrap_or(0.0)
+        ).unwrap();
+        if let Some(ref started_at) = command.started_at {
+            write!(&mut xml, " timestamp=\"{}\"", started_at).unwrap();
+        }
+        write!(&mut xml, ">\n").unwrap();
+
+        for step in command.steps.iter() {
+            write!(
+                &mut xml,
+                "    <testcase name=\"{}\" classname=\"{}\" time=\"{:.3}\">\n",
+                escape_xml(&step.command),
+                escape_xml(&command.name),
+                step.duration_secs.unwrap_or(0.0)
+            ).unwrap();
+            match step.status {
+                "failed" => write!(
+                    &mut xml,
+                    "      <failure message=\"`{}` returned {}\"/>\n",
+                    escape_xml(&step.command),
+                    step.exit_code.unwrap_or(0)
+                ).unwrap(),
+                "skipped" | "not_run" => write!(
+                    &mut xml,
+                    "      <skipped message=\"{}\"/>\n",
+                    escape_xml(skipped_reason(command.status, step.status))
+                ).unwrap(),
+                _ => {}
+            }
+            if let Some(ref output) = step.output {
+                write!(&mut xml, "      <system-out>{}</system-out>\n", escape_xml(output)).unwrap();
+            }
+            write!(&mut xml, "    </testcase>\n").unwrap();
+        }
+
+        write!(&mut xml, "  </testsuite>\n").unwrap();
+    }
+
+    write!(&mut xml, "</testsuites>\n").unwrap();
+    return xml;
+}
+
+fn skipped_reason(command_status: &str, step_status: &str) -> &'static str {
+    return match (command_status, step_status) {
+        ("up_to_date", _) => "The command was up to date",
+        (_, "skipped") => "`when` didn't match",
+        ("failed", _) => "An earlier step failed",
+        ("not_run", _) => "A dependency failed, or the run stopped",
+        _ => "The run stopped",
+    };
+}
+
+/**
+ * Control characters, like the escapes for colors, aren't allowed in XML at all, so they're
+ * dropped.
+ */
+fn escape_xml(value: &str) -> String {
+    let mut result = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '&' => result.push_str("&amp;"),
+            '<' => result.push_str("&lt;"),
+            '>' => result.push_str("&gt;"),
+            '"' => result.push_str("&quot;"),
+            '\'' => result.push_str("&apos;"),
+            '\t' | '\n' | '\r' => result.push(c),
+            _ if c.is_control() => {}
+            _ => result.push(c),
+        }
+    }
+
+    return result;
+}
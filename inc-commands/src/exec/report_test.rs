@@ -0,0 +1,111 @@
+#[cfg(test)]
+pub mod test {
+    use exec::report::*;
+    use std::fs;
+    use std::path::PathBuf;
+
+    fn step(command: &str, status: &'static str, exit_code: Option<i32>) -> StepReport {
+        return StepReport {
+            command: s!(command),
+            status: status,
+            started_at: None,
+            finished_at: None,
+            duration_secs: Some(0.5),
+            exit_code: exit_code,
+            attempts: 1,
+            output: None,
+            log_file: None,
+        };
+    }
+
+    fn report() -> Report {
+        return Report {
+            started_at: s!("2018-09-30T14:05:09.123Z"),
+            finished_at: s!("2018-09-30T14:05:11.123Z"),
+            duration_secs: 2.0,
+            exit_code: 1,
+            commands: vec![
+                CommandReport {
+                    name: s!("build"),
+                    defined_in: None,
+                    status: "failed",
+                    started_at: Some(s!("2018-09-30T14:05:09.123Z")),
+                    finished_at: Some(s!("2018-09-30T14:05:11.123Z")),
+                    duration_secs: Some(2.0),
+                    steps: vec![
+                        step("echo \"<ok>\" && echo \x1B[31mred", "succeeded", Some(0)),
+                        step("cargo build", "failed", Some(101)),
+                        step("cargo doc", "not_run", None),
+                    ],
+                },
+                CommandReport {
+                    name: s!("lint"),
+                    defined_in: None,
+                    status: "up_to_date",
+                    started_at: None,
+                    finished_at: None,
+                    duration_secs: None,
+                    steps: vec![step("cargo clippy", "skipped", None)],
+                },
+            ],
+        };
+    }
+
+    fn write(name: &str, format: ReportFormat) -> String {
+        let path = ::std::env::temp_dir()
+            .join(format!("inc-report-test-{}", ::std::process::id()))
+            .join(name);
+        write_report(
+            &report(),
+            &ReportOptions {
+                path: path.clone(),
+                format: format,
+                include_output: false,
+            },
+        ).unwrap();
+
+        let contents = fs::read_to_string(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        return contents;
+    }
+
+    #[test]
+    fn test_format_from_name() {
+        let xml = PathBuf::from("target/report.xml");
+        let json = PathBuf::from("target/report.json");
+        assert_eq!(ReportFormat::from_name(None, &xml), ReportFormat::Junit);
+        assert_eq!(ReportFormat::from_name(None, &json), ReportFormat::Json);
+        assert_eq!(ReportFormat::from_name(Some("json"), &xml), ReportFormat::Json);
+        assert_eq!(ReportFormat::from_name(Some("junit"), &json), ReportFormat::Junit);
+    }
+
+    #[test]
+    fn test_junit() {
+        let xml = write("report.xml", ReportFormat::Junit);
+        assert!(xml.starts_with("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n"));
+        assert!(
+            xml.contains(
+                "<testsuites name=\"inc exec\" tests=\"4\" failures=\"1\" skipped=\"2\" time=\"2.000\">"
+            ),
+            "{}",
+            xml
+        );
+        assert!(xml.contains(
+            "  <testsuite name=\"build\" tests=\"3\" failures=\"1\" skipped=\"1\" time=\"2.000\" timestamp=\"2018-09-30T14:05:09.123Z\">"
+        ));
+        assert!(xml.contains("<testcase name=\"echo &quot;&lt;ok&gt;&quot; &amp;&amp; echo [31mred\""));
+        assert!(xml.contains("<failure message=\"`cargo build` returned 101\"/>"));
+        assert!(xml.contains("<skipped message=\"An earlier step failed\"/>"));
+        assert!(xml.contains("<skipped message=\"The command was up to date\"/>"));
+        assert!(xml.ends_with("</testsuites>\n"));
+    }
+
+    #[test]
+    fn test_json() {
+        let json = write("report.json", ReportFormat::Json);
+        assert!(json.contains("\"exit_code\": 101"), "{}", json);
+        assert!(json.contains("\"status\": \"up_to_date\""));
+        assert!(!json.contains("\"output\""));
+        assert!(!json.contains("\"log_file\""));
+    }
+}
@@ -0,0 +1,1319 @@
+use chrono::{DateTime, SecondsFormat, Utc};
+use inc_lib::core::config::{env_values, Backoff, CommandAndEnv, ExecCommandConfig, ExitCodePolicy};
+use inc_lib::exec::executor::{
+    execute_command_line_with_output, CliError, CliResult, CommandLine, RunOptions,
+    RunningProcesses, TIMEOUT_EXIT_CODE,
+};
+use inc_lib::exec::wait::Wait;
+use inc_lib::libs::duration::parse_duration;
+use inc_lib::libs::secrets::Secrets;
+use inc_lib::libs::shell::{build_command_line, build_script_command_line, join_args, NO_SHELL};
+use super::condition::{evaluate, ConditionContext};
+use super::fingerprint::Fingerprint;
+use super::report::{write_report, CommandReport, Report, ReportOptions, StepReport};
+use super::services::{RunningService, ServicePlan};
+use std::collections::{BTreeMap, HashMap};
+use std::env;
+use std::fmt::Write;
+use std::fs;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{channel, Sender};
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+#[derive(Clone)]
+pub struct ExecOptions {
+    pub jobs: usize,
+    pub keep_going: bool,
+    pub force: bool,
+    pub report: Option<ReportOptions>,
+    pub log_dir: Option<PathBuf>,
+}
+
+/**
+ * How many lines of a failed step's log are shown.
+ */
+const LOG_TAIL_LINES: usize = 20;
+
+/**
+ * What a step fails with when inc couldn't run it.
+ */
+const STEP_ERROR_EXIT_CODE: i32 = 17;
+
+/**
+ * What a command fails with when one of it's services couldn't be started.
+ */
+const SERVICE_EXIT_CODE: i32 = 1;
+
+#[derive(Debug, PartialEq)]
+enum CommandState {
+    Waiting,
+    Running,
+    Succeeded,
+    Failed,
+}
+
+/**
+ * Why a command didn't run at all.
+ */
+#[derive(Debug, PartialEq)]
+enum SkipReason {
+    When,
+    UpToDate,
+}
+
+/**
+ * A command from the execution order, and the progress made running it's steps. The `finally`
+ * steps are at the end of `steps`, after the first `main_steps`.
+ */
+pub struct ScheduledCommand {
+    name: String,
+    config: ExecCommandConfig,
+    steps: Vec<CommandAndEnv>,
+    main_steps: usize,
+    command_lines: Vec<CommandLine>,
+    limits: Vec<StepLimits>,
+    working_dir: Option<PathBuf>,
+    working_dirs: Vec<Option<PathBuf>>,
+    waits: Vec<Option<Wait>>,
+    defined_in: Option<PathBuf>,
+    fingerprint: Option<Fingerprint>,
+    extra_args: String,
+    depends_on: Vec<usize>,
+    runs_after: Option<String>,
+    runs_after_index: Option<usize>,
+    state: CommandState,
+    next_step: usize,
+    running_steps: usize,
+    ignored_failures: Vec<i32>,
+    skipped: Option<SkipReason>,
+    records: Vec<Option<StepRecord>>,
+    serial_group: Option<String>,
+    secrets: Secrets,
+    finalizing: bool,
+    main_completed: bool,
+    finally_failure: Option<i32>,
+    services: Vec<ServicePlan>,
+    running_services: Vec<RunningService>,
+}
+
+impl ScheduledCommand {
+    pub fn new(
+        name: &str,
+        config: &ExecCommandConfig,
+        steps: Vec<CommandAndEnv>,
+        finally: Vec<CommandAndEnv>,
+        defined_in: Option<PathBuf>,
+        extra_args: String,
+    ) -> Result<Self, String> {
+        let main_steps = steps.len();
+        let mut steps = steps;
+        steps.extend(finally);
+        let mut command_lines: Vec<CommandLine> = Vec::new();
+        let records = steps.iter().map(|_| None).collect();
+        let mut limits: Vec<StepLimits> = Vec::new();
+        for step in steps.iter() {
+            match build_step_command_line(config, step) {
+                Ok(command_line) => command_lines.push(command_line),
+                Err(err) => return Err(format!("Unable to run a step in {}: {}", name, err)),
+            }
+            match build_step_limits(config, step) {
+                Ok(value) => limits.push(value),
+                Err(err) => return Err(format!("Unable to run a step in {}: {}", name, err)),
+            }
+        }
+
+        let project_dir = defined_in.as_ref().and_then(|x| x.parent());
+        let working_dir = config
+            .cwd
+            .as_ref()
+            .map(|cwd| resolve_working_dir(project_dir, cwd));
+        let working_dirs: Vec<Option<PathBuf>> = steps
+            .iter()
+            .map(|step| {
+                step.cwd
+                    .as_ref()
+                    .or(config.cwd.as_ref())
+                    .map(|cwd| resolve_working_dir(project_dir, cwd))
+            }).collect();
+
+        let mut waits: Vec<Option<Wait>> = Vec::new();
+        for (step, working_dir) in steps.iter().zip(working_dirs.iter()) {
+            let wait_for = match step.wait_for {
+                Some(ref value) => value,
+                None => {
+                    waits.push(None);
+                    continue;
+                }
+            };
+            let shell = step.shell.as_ref().or(config.shell.as_ref());
+            match Wait::new(wait_for, shell.map(|x| x.as_str()), working_dir.as_ref().map(|x| x.as_path())) {
+                Ok(value) => waits.push(Some(value)),
+                Err(err) => return Err(format!("Unable to run a step in {}: {}", name, err)),
+            }
+        }
+
+        let secrets = Secrets::new(config.secrets.iter().flat_map(|name| {
+            steps
+                .iter()
+                .filter_map(move |step| step.command_env.get(name).map(|x| s!(x.value())))
+                .chain(config.env.get(name).map(|x| s!(x.value())))
+                .chain(env::var(name).ok())
+        }));
+
+        let mut services: Vec<ServicePlan> = Vec::new();
+        for service in config.services.iter() {
+            let working_dir = service
+                .cwd
+                .as_ref()
+                .or(config.cwd.as_ref())
+                .map(|cwd| resolve_working_dir(project_dir, cwd));
+            match ServicePlan::new(service, config.shell.as_ref().map(|x| x.as_str()), working_dir) {
+                Ok(value) => services.push(value),
+                Err(err) => {
+                    return Err(format!(
+                        "Unable to run the service `{}` in {}: {}",
+                        service.name, name, err
+                    ))
+                }
+            }
+        }
+
+        let step_envs: Vec<BTreeMap<String, String>> = steps
+            .iter()
+            .map(|x| env_values(&x.command_env).into_iter().collect())
+            .collect();
+        let definition = format!(
+            "{:?}\n{:?}\n{:?}\n{}",
+            command_lines, working_dirs, step_envs, extra_args
+        );
+        let fingerprint = match project_dir {
+            Some(dir) if !config.inputs.is_empty() || !config.outputs.is_empty() => Some(
+                Fingerprint::new(dir, name, &config.inputs, &config.outputs, definition),
+            ),
+            _ => None,
+        };
+
+        return Ok(ScheduledCommand {
+            name: s!(name),
+            config: config.clone(),
+            steps: steps,
+            main_steps: main_steps,
+            command_lines: command_lines,
+            limits: limits,
+            working_dir: working_dir,
+            working_dirs: working_dirs,
+            waits: waits,
+            defined_in: defined_in.clone(),
+            fingerprint: fingerprint,
+            extra_args: extra_args,
+            depends_on: Vec::new(),
+            runs_after: None,
+            runs_after_index: None,
+            state: CommandState::Waiting,
+            next_step: 0,
+            running_steps: 0,
+            ignored_failures: Vec::new(),
+            skipped: None,
+            records: records,
+            serial_group: None,
+            secrets: secrets,
+            finalizing: false,
+            main_completed: false,
+            finally_failure: None,
+            services: services,
+            running_services: Vec::new(),
+        });
+    }
+
+    /**
+     * Commands in the same group never run at the same time, even when they don't depend on
+     * each other. They start in the order they were scheduled.
+     */
+    pub fn set_serial_group(&mut self, group: &str) {
+        self.serial_group = Some(s!(group));
+    }
+
+    /**
+     * Waits for `name` to be done, like a dependency, but also runs when it failed, with
+     * `--keep-going`.
+     */
+    pub fn set_runs_after(&mut self, name: &str) {
+        self.runs_after = Some(s!(name));
+    }
+
+    /**
+     * `finally` steps always run one at a time.
+     */
+    fn can_start_step(&self) -> bool {
+        if self.next_step >= self.steps.len() {
+            return false;
+        }
+        if self.finalizing {
+            return self.running_steps == 0;
+        }
+        if self.state != CommandState::Running || self.next_step >= self.main_steps {
+            return false;
+        }
+
+        return self.config.parallel || self.running_steps == 0;
+    }
+
+    /**
+     * The `finally` steps start once the command has stopped running it's other steps, because
+     * they are done, one of them failed, or the whole run is stopping.
+     */
+    fn needs_finally(&self, stopping: bool) -> bool {
+        if self.finalizing || self.main_steps == self.steps.len() || self.running_steps > 0 {
+            return false;
+        }
+
+        return match self.state {
+            CommandState::Running => stopping || self.next_step >= self.main_steps,
+            CommandState::Failed => true,
+            _ => false,
+        };
+    }
+
+    /**
+     * True once nothing else will run for the command, so it's services can be stopped.
+     */
+    fn is_done(&self, stopping: bool) -> bool {
+        if self.running_steps > 0 || self.needs_finally(stopping) {
+            return false;
+        }
+        if self.finalizing {
+            return self.next_step >= self.steps.len();
+        }
+
+        return match self.state {
+            CommandState::Succeeded | CommandState::Failed => true,
+            CommandState::Running => stopping,
+            CommandState::Waiting => false,
+        };
+    }
+
+    fn stop_services(&mut self) {
+        while let Some(service) = self.running_services.pop() {
+            service.stop();
+        }
+    }
+
+    fn is_up_to_date(&self) -> bool {
+        return match self.fingerprint {
+            Some(ref fingerprint) => fingerprint.is_up_to_date(),
+            None => false,
+        };
+    }
+
+    fn is_finished(&self) -> bool {
+        return self.next_step >= self.steps.len() && self.running_steps == 0;
+    }
+
+    /**
+     * The environment a step runs with, on top of inc's own.
+     */
+    fn step_env(&self, step: usize) -> HashMap<String, String> {
+        let mut env: HashMap<String, String> = HashMap::new();
+
+        for (key, value) in self.steps[step].command_env.iter() {
+            env.insert(key.clone(), s!(value.value()));
+        }
+        env.insert(s!("INC_ARGS"), self.extra_args.clone());
+        if let Some(ref path) = self.defined_in {
+            env.insert(
+                s!("INC_PROJECT_DIR"),
+                s!(path.parent().unwrap().to_str().unwrap()),
+            );
+        }
+
+        return env;
+    }
+
+    /**
+     * Masks the secrets of the other commands too, because everything inc runs can see the
+     * variables from inc's own environment.
+     */
+    fn add_secrets(&mut self, secrets: &Secrets) {
+        self.secrets = self.secrets.merge(secrets);
+    }
+
+    /**
+     * Everything about how the command would run, for `--dry-run`. `when` and if the command
+     * is up to date are only checked when it really runs.
+     */
+    pub fn describe(&self, position: usize) -> String {
+        let mut description = String::new();
+        let defined_in = match self.defined_in {
+            Some(ref path) => s!(path.to_string_lossy()),
+            None => s!("an unknown file"),
+        };
+        write!(&mut description, "{}. `{}` from {}\n", position, self.name, defined_in).unwrap();
+        for service in self.services.iter() {
+            write!(&mut description, "   service: {}\n", service.describe()).unwrap();
+        }
+        if !self.config.depends_on.is_empty() {
+            write!(
+                &mut description,
+                "   depends on: {}\n",
+                self.config.depends_on.join(", ")
+            ).unwrap();
+        }
+
+        let current_dir = env::current_dir().ok();
+        for (i, step) in self.steps.iter().enumerate() {
+            let command_line = &self.command_lines[i];
+            let configured_shell = step.shell.as_ref().or(self.config.shell.as_ref());
+            let shell = match command_line {
+                CommandLine::Shell(_) => command_line.to_args()[0].clone(),
+                CommandLine::Script { interpreter, .. } => s!(interpreter.to_string_lossy()),
+                CommandLine::Direct(_, _) => match configured_shell {
+                    Some(shell) if step.argv.is_empty() => shell.clone(),
+                    _ => s!(NO_SHELL),
+                },
+            };
+            let working_dir = self.working_dirs[i].clone().or(current_dir.clone());
+
+            let label = if i >= self.main_steps { " (finally)" } else { "" };
+            write!(&mut description, "   {}.{} `{}`{}\n", position, i + 1, step.display_command(), label).unwrap();
+            if let Some(ref wait) = self.waits[i] {
+                write!(&mut description, "       wait: {}, for up to {}\n", wait.describe(), wait.timeout()).unwrap();
+            } else {
+                write!(&mut description, "       run: {}\n", join_args(&command_line.to_args())).unwrap();
+                write!(&mut description, "       shell: {}\n", shell).unwrap();
+            }
+            if let Some(dir) = working_dir {
+                write!(&mut description, "       cwd: {}\n", dir.to_string_lossy()).unwrap();
+            }
+            if let Some((ref timeout, _)) = self.limits[i].timeout {
+                write!(&mut description, "       timeout: {}\n", timeout).unwrap();
+            }
+            if let Some(ref retries) = self.limits[i].retries {
+                write!(&mut description, "       retries: {}\n", retries.count).unwrap();
+            }
+
+            let step_env: BTreeMap<String, String> = self.step_env(i).into_iter().collect();
+            let env_changes: Vec<String> = step_env
+                .iter()
+                .filter_map(|(key, value)| match env::var(key) {
+                    Ok(ref current) if current == value => None,
+                    Ok(_) => Some(format!("~{}={}", key, value)),
+                    Err(_) => Some(format!("+{}={}", key, value)),
+                }).collect();
+            if !env_changes.is_empty() {
+                write!(&mut description, "       env:\n").unwrap();
+                for change in env_changes {
+                    write!(&mut description, "         {}\n", change).unwrap();
+                }
+            }
+
+            if let CommandLine::Script { ref contents, .. } = command_line {
+                write!(&mut description, "       script:\n").unwrap();
+                for line in contents.lines() {
+                    write!(&mut description, "         {}\n", line).unwrap();
+                }
+            }
+        }
+
+        return self.secrets.mask(&description);
+    }
+
+    /**
+     * What happened to the command, and each of it's steps, for `--report`.
+     */
+    fn report(&self) -> CommandReport {
+        let status = match (&self.skipped, &self.state) {
+            (Some(SkipReason::When), _) => "skipped",
+            (Some(SkipReason::UpToDate), _) => "up_to_date",
+            (None, CommandState::Succeeded) => "succeeded",
+            (None, CommandState::Failed) => "failed",
+            (None, CommandState::Running) => "stopped",
+            (None, CommandState::Waiting) => "not_run",
+        };
+
+        let steps: Vec<StepReport> = self
+            .steps
+            .iter()
+            .zip(self.records.iter())
+            .map(|(step, record)| match record {
+                Some(record) => StepReport {
+                    command: self.secrets.mask(&step.display_command()),
+                    status: match record.exit_code {
+                        _ if record.skipped => "skipped",
+                        0 => "succeeded",
+                        _ => "failed",
+                    },
+                    started_at: Some(format_timestamp(record.started)),
+                    finished_at: Some(format_timestamp(record.finished)),
+                    duration_secs: Some(seconds_between(record.started, record.finished)),
+                    exit_code: if record.skipped {
+                        None
+                    } else {
+                        Some(record.exit_code)
+                    },
+                    attempts: record.attempts,
+                    output: record.output.clone(),
+                    log_file: record.log_file.as_ref().map(|x| s!(x.to_string_lossy())),
+                },
+                None => StepReport {
+                    command: self.secrets.mask(&step.display_command()),
+                    status: if self.skipped.is_some() {
+                        "skipped"
+                    } else {
+                        "not_run"
+                    },
+                    started_at: None,
+                    finished_at: None,
+                    duration_secs: None,
+                    exit_code: None,
+                    attempts: 0,
+                    output: None,
+                    log_file: None,
+                },
+            }).collect();
+
+        let started = self.records.iter().flat_map(|x| x).map(|x| x.started).min();
+        let finished = self.records.iter().flat_map(|x| x).map(|x| x.finished).max();
+
+        return CommandReport {
+            name: self.name.clone(),
+            defined_in: self.defined_in.as_ref().map(|x| s!(x.to_string_lossy())),
+            status: status,
+            started_at: started.map(format_timestamp),
+            finished_at: finished.map(format_timestamp),
+            duration_secs: match (started, finished) {
+                (Some(started), Some(finished)) => Some(seconds_between(started, finished)),
+                _ => None,
+            },
+            steps: steps,
+        };
+    }
+
+    fn step_label(&self, step: usize) -> String {
+        if self.steps.len() > 1 {
+            return format!("{}:{}", self.name, step + 1);
+        }
+
+        return s!(self.name);
+    }
+
+    /**
+     * Like `build.2.log`.
+     */
+    fn log_file_name(&self, step: usize) -> String {
+        return format!("{}.{}.log", safe_file_name(&self.name), step + 1);
+    }
+}
+
+pub fn build_step_command_line(
+    config: &ExecCommandConfig,
+    step: &CommandAndEnv,
+) -> Result<CommandLine, String> {
+    let shell = step.shell.as_ref().or(config.shell.as_ref());
+
+    // Never run, but it makes what the step waits for part of the fingerprint.
+    if step.wait_for.is_some() {
+        return Ok(CommandLine::Shell(step.display_command()));
+    }
+
+    if let Some(ref script) = step.script {
+        return build_script_command_line(shell.map(|x| x.as_str()), script, &step.argv);
+    }
+
+    // Likely a typo, like `comand`, which would otherwise run nothing and succeed.
+    if step.argv.is_empty() && step.command.trim().is_empty() {
+        return Err(s!("a step needs a command, a script or argv"));
+    }
+
+    if !step.argv.is_empty() {
+        if !step.command.is_empty() {
+            return Err(s!("a step can have a command or argv, not both"));
+        }
+
+        return Ok(CommandLine::Direct(
+            PathBuf::from(&step.argv[0]),
+            step.argv[1..].to_vec(),
+        ));
+    }
+
+    return build_command_line(shell.map(|x| x.as_str()), &step.command);
+}
+
+/**
+ * How long a step may run, and how often it's retried, after parsing the durations.
+ */
+#[derive(Clone)]
+pub struct StepLimits {
+    pub timeout: Option<(String, Duration)>,
+    pub retries: Option<Retry>,
+}
+
+#[derive(Clone)]
+pub struct Retry {
+    count: u32,
+    backoff: Backoff,
+    delay: Duration,
+}
+
+impl Retry {
+    pub fn delay_before(&self, attempt: u32) -> Duration {
+        return match self.backoff {
+            Backoff::Constant => self.delay,
+            Backoff::Exponential => self.delay * 2u32.saturating_pow(attempt),
+        };
+    }
+}
+
+/**
+ * The step's `timeout` and `retries` win over the command's.
+ */
+pub fn build_step_limits(config: &ExecCommandConfig, step: &CommandAndEnv) -> Result<StepLimits, String> {
+    let timeout = match step.timeout.as_ref().or(config.timeout.as_ref()) {
+        Some(value) => match parse_duration(value) {
+            Ok(duration) => Some((value.clone(), duration)),
+            Err(err) => return Err(format!("invalid timeout, {}", err)),
+        },
+        None => None,
+    };
+
+    let retries = match step.retries.as_ref().or(config.retries.as_ref()) {
+        Some(retries) => match parse_duration(&retries.delay) {
+            Ok(delay) => Some(Retry {
+                count: retries.count,
+                backoff: retries.backoff.clone(),
+                delay: delay,
+            }),
+            Err(err) => return Err(format!("invalid retry delay, {}", err)),
+        },
+        None => None,
+    };
+
+    return Ok(StepLimits {
+        timeout: timeout,
+        retries: retries,
+    });
+}
+
+/**
+ * Relative paths are from the directory of the `inc.yaml` that defined the command.
+ */
+pub fn resolve_working_dir(project_dir: Option<&Path>, cwd: &str) -> PathBuf {
+    let path = PathBuf::from(cwd);
+    if path.is_absolute() {
+        return path;
+    }
+
+    return match project_dir {
+        Some(dir) => dir.join(path),
+        None => path,
+    };
+}
+
+/**
+ * Replaces anything that doesn't belong in a file name, like the `[` and `=` of a matrix
+ * combination, by `_`.
+ */
+pub fn safe_file_name(name: &str) -> String {
+    return name
+        .chars()
+        .map(|x| match x {
+            '-' | '_' | '.' => x,
+            _ if x.is_ascii_alphanumeric() => x,
+            _ => '_',
+        }).collect();
+}
+
+/**
+ * Shows the end of a failed step's log, so the reason it failed is close to the error even
+ * when other steps were writing at the same time.
+ */
+fn show_log_tail(log_file: &Path) {
+    let contents = match fs::read_to_string(log_file) {
+        Ok(contents) => contents,
+        Err(err) => {
+            warn!("Unable to read the log {:?}: {}", log_file, err);
+            return;
+        }
+    };
+
+    let lines: Vec<&str> = contents.lines().collect();
+    let start = lines.len().saturating_sub(LOG_TAIL_LINES);
+    if !lines.is_empty() {
+        error!("Last {} lines of the output:", lines.len() - start);
+        for line in lines[start..].iter() {
+            error!("    {}", line);
+        }
+    }
+    error!("The full output is in {}", log_file.to_string_lossy());
+}
+
+/**
+ * Formats the time as UTC, like `2018-09-30T14:05:09.123Z`.
+ */
+pub fn format_timestamp(time: SystemTime) -> String {
+    return DateTime::<Utc>::from(time).to_rfc3339_opts(SecondsFormat::Millis, true);
+}
+
+fn seconds_between(started: SystemTime, finished: SystemTime) -> f64 {
+    let duration = finished
+        .duration_since(started)
+        .unwrap_or(Duration::from_secs(0));
+    return duration.as_secs() as f64 + duration.subsec_nanos() as f64 / 1e9;
+}
+
+struct StepResult {
+    command: usize,
+    step: usize,
+    exit_code: i32,
+    record: StepRecord,
+}
+
+/**
+ * When a step ran, and how it went. Retries are included, and `output` has the output from
+ * every attempt.
+ */
+struct StepRecord {
+    started: SystemTime,
+    finished: SystemTime,
+    exit_code: i32,
+    attempts: u32,
+    skipped: bool,
+    output: Option<String>,
+    log_file: Option<PathBuf>,
+}
+
+struct Scheduler {
+    commands: Vec<ScheduledCommand>,
+    options: ExecOptions,
+    run_log_dir: Option<PathBuf>,
+    processes: RunningProcesses,
+    /**
+     * The `finally` steps, which aren't stopped when another command fails.
+     */
+    cleanup_processes: RunningProcesses,
+    running_steps: usize,
+    stopping: bool,
+    exit_code: Option<i32>,
+    failed_steps: Vec<(String, i32)>,
+}
+
+/**
+ * Runs the commands, which must be in dependency order. A command starts once all of
+ * it's dependencies have succeeded, and at most `jobs` steps run at the same time. Once
+ * `processes` is interrupted, nothing else is started.
+ */
+pub fn run_commands(
+    commands: Vec<ScheduledCommand>,
+    options: ExecOptions,
+    processes: RunningProcesses,
+) -> CliResult {
+    let mut commands = commands;
+    let positions: HashMap<String, usize> = commands
+        .iter()
+        .enumerate()
+        .map(|(i, command)| (command.name.clone(), i))
+        .collect();
+    for command in commands.iter_mut() {
+        command.depends_on = command
+            .config
+            .depends_on
+            .iter()
+            .filter_map(|x| positions.get(x).cloned())
+            .collect();
+        command.runs_after_index = command
+            .runs_after
+            .as_ref()
+            .and_then(|x| positions.get(x).cloned());
+    }
+
+    let secrets = commands
+        .iter()
+        .fold(Secrets::default(), |secrets, command| secrets.merge(&command.secrets));
+    for command in commands.iter_mut() {
+        command.add_secrets(&secrets);
+    }
+
+    let mut scheduler = Scheduler {
+        commands: commands,
+        options: options,
+        run_log_dir: None,
+        processes: processes,
+        cleanup_processes: RunningProcesses::new(),
+        running_steps: 0,
+        stopping: false,
+        exit_code: None,
+        failed_steps: Vec::new(),
+    };
+
+    return scheduler.run();
+}
+
+impl Scheduler {
+    fn run(&mut self) -> CliResult {
+        let started = SystemTime::now();
+        if let Some(ref log_dir) = self.options.log_dir {
+            let run_log_dir = log_dir.join(format_timestamp(started).replace(':', "-"));
+            if let Err(err) = fs::create_dir_all(&run_log_dir) {
+                return Err(CliError::new(
+                    1,
+                    format!("Unable to create the log directory {:?}: {}", run_log_dir, err),
+                ));
+            }
+            self.run_log_dir = Some(run_log_dir);
+        }
+        let result = self.run_steps();
+
+        if let Some(ref options) = self.options.report {
+            let exit_code = match result {
+                Ok(code) => code,
+                Err(ref err) => err.code,
+            };
+            let finished = SystemTime::now();
+            let report = Report {
+                started_at: format_timestamp(started),
+                finished_at: format_timestamp(finished),
+                duration_secs: seconds_between(started, finished),
+                exit_code: exit_code,
+                commands: self.commands.iter().map(|x| x.report()).collect(),
+            };
+            if let Err(err) = write_report(&report, options) {
+                return Err(CliError::new(
+                    1,
+                    format!("Unable to write the report to {:?}: {}", options.path, err),
+                ));
+            }
+        }
+
+        return result;
+    }
+
+    /**
+     * A control-c cancels the run, even when it's between steps, or waiting to retry one.
+     */
+    fn run_steps(&mut self) -> CliResult {
+        let (sender, receiver) = channel::<StepResult>();
+        let _interrupt_handler = self.processes.cancel_on_interrupt();
+
+        loop {
+            if self.is_cancelled() {
+                self.stopping = true;
+            }
+
+            self.start_steps(&sender);
+
+            if self.running_steps == 0 {
+                break;
+            }
+
+            let result = receiver.recv().unwrap();
+            self.running_steps -= 1;
+            self.finish_step(result);
+        }
+
+        for command in self.commands.iter_mut() {
+            command.stop_services();
+        }
+
+        if self.is_cancelled() {
+            info!("Cancelled");
+            return Ok(130);
+        }
+
+        self.report_failures();
+
+        return Ok(self.exit_code.unwrap_or(0));
+    }
+
+    fn is_cancelled(&self) -> bool {
+        return self.processes.is_cancelled() || self.cleanup_processes.is_cancelled();
+    }
+
+    /**
+     * Once the run is stopping, only `finally` steps are started.
+     */
+    fn start_steps(&mut self, sender: &Sender<StepResult>) {
+        let command_count = self.commands.len();
+
+        for index in 0..command_count {
+            if self.running_steps >= self.options.jobs {
+                return;
+            }
+
+            if !self.stopping && self.commands[index].state == CommandState::Waiting {
+                let is_ready = self.commands[index]
+                    .depends_on
+                    .iter()
+                    .all(|x| self.commands[*x].state == CommandState::Succeeded)
+                    && self.commands[index]
+                        .runs_after_index
+                        .map_or(true, |x| self.is_settled(x));
+                if !is_ready || self.is_group_busy(index) {
+                    continue;
+                }
+
+                if !self.should_run(index) {
+                    info!(
+                        "*** Skipping `{}`, `when` didn't match",
+                        self.commands[index].name
+                    );
+                    self.commands[index].state = CommandState::Succeeded;
+                    self.commands[index].skipped = Some(SkipReason::When);
+                    continue;
+                }
+
+                if !self.options.force && self.commands[index].is_up_to_date() {
+                    info!(
+                        "*** `{}` is up to date, skipping",
+                        self.commands[index].name
+                    );
+                    self.commands[index].state = CommandState::Succeeded;
+                    self.commands[index].skipped = Some(SkipReason::UpToDate);
+                    continue;
+                }
+
+                if command_count > 1 {
+                    info!("*** Running `{}`", self.commands[index].name);
+                }
+                self.commands[index].state = CommandState::Running;
+                if let Err(err) = self.start_services(index) {
+                    error!("{}", err);
+                    self.commands[index].state = CommandState::Failed;
+                    self.record_failure(SERVICE_EXIT_CODE);
+                } else if self.commands[index].is_finished() {
+                    self.finish_command(index);
+                }
+            }
+
+            if self.commands[index].needs_finally(self.stopping) {
+                let command = &mut self.commands[index];
+                info!("*** Running the `finally` steps of `{}`", command.name);
+                command.main_completed = command.state == CommandState::Running && !self.stopping;
+                command.finalizing = true;
+                command.next_step = command.main_steps;
+            }
+            if !self.commands[index].running_services.is_empty()
+                && self.commands[index].is_done(self.stopping)
+            {
+                self.commands[index].stop_services();
+            }
+            if self.stopping && !self.commands[index].finalizing {
+                continue;
+            }
+
+            while self.running_steps < self.options.jobs && self.commands[index].can_start_step() {
+                let step = self.commands[index].next_step;
+                self.commands[index].next_step += 1;
+                self.commands[index].running_steps += 1;
+                self.running_steps += 1;
+                self.start_step(index, step, sender.clone());
+            }
+        }
+    }
+
+    /**
+     * Starts the command's services, in order, and waits for each of them to be ready.
+     */
+    fn start_services(&mut self, index: usize) -> Result<(), String> {
+        for i in 0..self.commands[index].services.len() {
+            let command = &self.commands[index];
+            let service = &command.services[i];
+            let mut env: HashMap<String, String> = HashMap::new();
+            if let Some(ref path) = command.defined_in {
+                env.insert(
+                    s!("INC_PROJECT_DIR"),
+                    s!(path.parent().unwrap().to_string_lossy()),
+                );
+            }
+            let options = RunOptions {
+                working_dir: None,
+                prefix: None,
+                quiet: false,
+                timeout: None,
+                record_output: false,
+                log_file: self.run_log_dir.as_ref().map(|dir| {
+                    dir.join(format!("{}.{}.log", safe_file_name(&command.name), safe_file_name(&service.name)))
+                }),
+                secrets: command.secrets.clone(),
+            };
+
+            let running = service.start(&env, &options, &self.processes)?;
+            self.commands[index].running_services.push(running);
+        }
+
+        return Ok(());
+    }
+
+    /**
+     * True once the command is done, or won't ever run, because one of it's dependencies
+     * failed.
+     */
+    fn is_settled(&self, index: usize) -> bool {
+        return match self.commands[index].state {
+            CommandState::Succeeded | CommandState::Failed => true,
+            CommandState::Running => false,
+            CommandState::Waiting => self.commands[index].depends_on.iter().any(|x| {
+                let state = &self.commands[*x].state;
+                *state == CommandState::Failed
+                    || (*state == CommandState::Waiting && self.is_settled(*x))
+            }),
+        };
+    }
+
+    fn is_group_busy(&self, index: usize) -> bool {
+        return match self.commands[index].serial_group {
+            Some(ref group) => self.commands.iter().any(|x| {
+                x.state == CommandState::Running && x.serial_group.as_ref() == Some(group)
+            }),
+            None => false,
+        };
+    }
+
+    fn should_run(&self, index: usize) -> bool {
+        let command = &self.commands[index];
+        return match command.config.when {
+            Some(ref when) => evaluate(
+                when,
+                &ConditionContext {
+                    working_dir: command.working_dir.clone(),
+                    shell: command.config.shell.as_ref().map(|x| x.as_str()),
+                    env: &env_values(&command.config.env),
+                    processes: &self.processes,
+                    secrets: &command.secrets,
+                },
+            ),
+            None => true,
+        };
+    }
+
+    fn start_step(&self, index: usize, step: usize, sender: Sender<StepResult>) {
+        let command = &self.commands[index];
+        let command_entry = command.steps[step].clone();
+        let run_options = RunOptions {
+            working_dir: command.working_dirs[step].clone(),
+            prefix: if self.options.jobs > 1 {
+                Some(command.step_label(step))
+            } else {
+                None
+            },
+            quiet: false,
+            timeout: command.limits[step].timeout.as_ref().map(|x| x.1),
+            record_output: self
+                .options
+                .report
+                .as_ref()
+                .map_or(false, |x| x.include_output),
+            log_file: match command.waits[step] {
+                Some(_) => None,
+                None => self
+                    .run_log_dir
+                    .as_ref()
+                    .map(|dir| dir.join(command.log_file_name(step))),
+            },
+            secrets: command.secrets.clone(),
+        };
+        let limits = command.limits[step].clone();
+        let wait = command.waits[step].clone();
+
+        let display_command = command.secrets.mask(&command_entry.display_command());
+        let log_prefix = match run_options.prefix {
+            Some(ref label) => format!("[{}] ", label),
+            None => s!(""),
+        };
+        let show_progress = command.steps.len() > 1;
+        let shell = command_entry.shell.clone().or(command.config.shell.clone());
+
+        let command_line = command.command_lines[step].clone();
+        let extra_env = command.step_env(step);
+
+        debug!(
+            "Executing {} in {:?} defined in {:?}",
+            command.secrets.mask(&format!("{:?}", command_line)),
+            run_options.working_dir,
+            command.defined_in
+        );
+
+        let processes = if step >= command.main_steps {
+            self.cleanup_processes.clone()
+        } else {
+            self.processes.clone()
+        };
+        let failed_command = display_command.clone();
+        thread::spawn(move || {
+            let started = SystemTime::now();
+            // The scheduler waits for a result from every step, so it gets one even after a panic
+            let result = panic::catch_unwind(AssertUnwindSafe(|| -> StepResult {
+                if let Some(ref when) = command_entry.when {
+                    let context = ConditionContext {
+                        working_dir: run_options.working_dir.clone(),
+                        shell: shell.as_ref().map(|x| x.as_str()),
+                        env: &extra_env,
+                        processes: &processes,
+                        secrets: &run_options.secrets,
+                    };
+                    if !evaluate(when, &context) {
+                        info!(
+                            "{}** Skipping `{}`, `when` didn't match",
+                            log_prefix, display_command
+                        );
+                        return StepResult {
+                            command: index,
+                            step: step,
+                            exit_code: 0,
+                            record: StepRecord {
+                                started: started,
+                                finished: SystemTime::now(),
+                                exit_code: 0,
+                                attempts: 0,
+                                skipped: true,
+                                output: None,
+                                log_file: None,
+                            },
+                        };
+                    }
+                }
+
+                if show_progress {
+                    info!("{}** Executing `{}`", log_prefix, display_command);
+                }
+
+                let mut attempt = 0;
+                let mut output: Option<String> = None;
+                let exit_code = loop {
+                    let (result, attempt_output) = match wait {
+                        Some(ref wait) => (wait.run(&extra_env, &run_options, &processes).map(|_| 0), None),
+                        None => execute_command_line_with_output(
+                            &command_line,
+                            extra_env.clone(),
+                            &run_options,
+                            &processes,
+                        ),
+                    };
+                    if let Some(attempt_output) = attempt_output {
+                        output = Some(output.unwrap_or_default() + &attempt_output);
+                    }
+
+                    let exit_code = match result {
+                        Ok(value) => value,
+                        Err(ref err) if wait.is_some() => {
+                            error!("{}{}", log_prefix, err.message);
+                            err.code
+                        }
+                        Err(ref err) if err.code == TIMEOUT_EXIT_CODE => {
+                            error!(
+                                "{}`{}` timed out after {}",
+                                log_prefix,
+                                display_command,
+                                limits.timeout.as_ref().unwrap().0
+                            );
+                            TIMEOUT_EXIT_CODE
+                        }
+                        Err(err) => {
+                            error!(
+                                "Error while executing `{}`! {}",
+                                display_command, err.message
+                            );
+                            17
+                        }
+                    };
+
+                    match limits.retries {
+                        Some(ref retries)
+                            if exit_code != 0 && attempt < retries.count && !processes.is_stopping() =>
+                        {
+                            let delay = retries.delay_before(attempt);
+                            attempt += 1;
+                            warn!(
+                                "{}`{}` returned {}, retrying in {:?} ({} of {})",
+                                log_prefix, display_command, exit_code, delay, attempt, retries.count
+                            );
+                            if !processes.sleep(delay) {
+                                break exit_code;
+                            }
+                        }
+                        _ => break exit_code,
+                    }
+                };
+
+                return StepResult {
+                    command: index,
+                    step: step,
+                    exit_code: exit_code,
+                    record: StepRecord {
+                        started: started,
+                        finished: SystemTime::now(),
+                        exit_code: exit_code,
+                        attempts: attempt + 1,
+                        skipped: false,
+                        output: output,
+                        log_file: run_options.log_file.clone(),
+                    },
+                };
+            }));
+
+            let _ = sender.send(result.unwrap_or_else(|_| {
+                error!("Error while executing `{}`! The step panicked", failed_command);
+                StepResult {
+                    command: index,
+                    step: step,
+                    exit_code: STEP_ERROR_EXIT_CODE,
+                    record: StepRecord {
+                        started: started,
+                        finished: SystemTime::now(),
+                        exit_code: STEP_ERROR_EXIT_CODE,
+                        attempts: 1,
+                        skipped: false,
+                        output: None,
+                        log_file: None,
+                    },
+                }
+            }));
+        });
+    }
+
+    fn finish_step(&mut self, result: StepResult) {
+        self.commands[result.command].running_steps -= 1;
+        self.commands[result.command].records[result.step] = Some(result.record);
+
+        if result.step >= self.commands[result.command].main_steps {
+            self.finish_finally_step(result.command, result.step, result.exit_code);
+            return;
+        }
+
+        if self.stopping || self.is_cancelled() {
+            debug!(
+                "Stopped `{}` after another command failed, or the run was cancelled",
+                self.commands[result.command].step_label(result.step)
+            );
+            return;
+        }
+
+        if result.exit_code != 0 {
+            let command = &mut self.commands[result.command];
+            let command_entry = &command.steps[result.step];
+            let ignore_failures = command_entry
+                .ignore_failures
+                .unwrap_or_else(|| command.config.ignore_failures);
+
+            let display_command = command.secrets.mask(&command_entry.display_command());
+            self.failed_steps
+                .push((display_command.clone(), result.exit_code));
+
+            if ignore_failures {
+                warn!(
+                    "Command: `{}` returned {}, continuing",
+                    display_command, result.exit_code
+                );
+                command.ignored_failures.push(result.exit_code);
+            } else {
+                error!(
+                    "Command: `{}` returned {}",
+                    display_command, result.exit_code
+                );
+                if let Some(ref log_file) = command.records[result.step]
+                    .as_ref()
+                    .and_then(|x| x.log_file.as_ref())
+                {
+                    show_log_tail(log_file);
+                }
+                command.state = CommandState::Failed;
+                self.record_failure(result.exit_code);
+                return;
+            }
+        }
+
+        if self.commands[result.command].state == CommandState::Running
+            && self.commands[result.command].is_finished()
+        {
+            self.finish_command(result.command);
+        }
+    }
+
+    /**
+     * A failed `finally` step doesn't stop the ones after it. The command fails with it's exit
+     * code, unless the command had already failed with another one.
+     */
+    fn finish_finally_step(&mut self, index: usize, step: usize, exit_code: i32) {
+        let command = &mut self.commands[index];
+        if exit_code != 0 {
+            let display_command = command
+                .secrets
+                .mask(&command.steps[step].display_command());
+            error!("Finally: `{}` returned {}", display_command, exit_code);
+            self.failed_steps.push((display_command, exit_code));
+            if command.finally_failure.is_none() {
+                command.finally_failure = Some(exit_code);
+            }
+        }
+
+        if !command.is_finished() {
+            return;
+        }
+
+        if command.main_completed {
+            self.finish_command(index);
+        } else if let Some(exit_code) = command.finally_failure {
+            if self.exit_code.is_none() {
+                self.exit_code = Some(exit_code);
+            }
+        }
+    }
+
+    fn finish_command(&mut self, index: usize) {
+        let failure = match self.commands[index].config.exit_code {
+            ExitCodePolicy::FailIfAny => self.commands[index].ignored_failures.first().cloned(),
+            ExitCodePolicy::AlwaysSucceed => None,
+        }.or(self.commands[index].finally_failure);
+
+        match failure {
+            Some(exit_code) => {
+                self.commands[index].state = CommandState::Failed;
+                self.record_failure(exit_code);
+            }
+            None => {
+                self.commands[index].state = CommandState::Succeeded;
+                if let Some(ref fingerprint) = self.commands[index].fingerprint {
+                    if let Err(err) = fingerprint.save() {
+                        warn!(
+                            "Unable to save the fingerprint for `{}`: {}",
+                            self.commands[index].name, err
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    fn record_failure(&mut self, exit_code: i32) {
+        if self.exit_code.is_none() {
+            self.exit_code = Some(exit_code);
+        }
+
+        if !self.options.keep_going {
+            self.stopping = true;
+            if self.running_steps > 0 {
+                self.processes.terminate_all();
+            }
+        }
+    }
+
+    fn report_failures(&self) {
+        let ignored_failures = self.commands.iter().any(|x| !x.ignored_failures.is_empty());
+        if ignored_failures || (self.options.keep_going && !self.failed_steps.is_empty()) {
+            let failure_summary: Vec<String> = self
+                .failed_steps
+                .iter()
+                .map(|(command, code)| format!(" - `{}` returned {}", command, code))
+                .collect();
+            warn!("Failed commands:\n{}", failure_summary.join("\n"));
+        }
+
+        if self.options.keep_going {
+            for command in self.commands.iter() {
+                if command.state == CommandState::Waiting {
+                    warn!("Skipped `{}` because a dependency failed", command.name);
+                }
+            }
+        }
+    }
+}
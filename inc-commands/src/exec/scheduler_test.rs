@@ -0,0 +1,149 @@
+#[cfg(test)]
+pub mod test {
+    use chrono::{TimeZone, Utc};
+    use exec::scheduler::*;
+    use inc_lib::core::config::{CommandAndEnv, ExecCommandConfig};
+    use inc_lib::exec::executor::CommandLine;
+    use serde_yaml;
+    use std::path::{Path, PathBuf};
+    use std::time::{Duration, SystemTime};
+
+    fn command(yaml: &str) -> ExecCommandConfig {
+        return serde_yaml::from_str::<ExecCommandConfig>(yaml).unwrap();
+    }
+
+    fn steps(config: &ExecCommandConfig) -> Vec<CommandAndEnv> {
+        return config
+            .commands
+            .iter()
+            .map(|x| x.clone().to_command_and_envs())
+            .collect();
+    }
+
+    #[test]
+    fn test_build_step_command_line() {
+        let config = command(
+            "shell: bash
+commands:
+    - echo hello
+    - argv: [cat, a file]
+    - command: echo direct
+      shell: none",
+        );
+        let steps = steps(&config);
+
+        assert_eq!(
+            build_step_command_line(&config, &steps[0]),
+            Ok(CommandLine::Direct(
+                PathBuf::from("bash"),
+                vec![s!("-c"), s!("echo hello")]
+            ))
+        );
+        assert_eq!(
+            build_step_command_line(&config, &steps[1]),
+            Ok(CommandLine::Direct(PathBuf::from("cat"), vec![s!("a file")]))
+        );
+        assert_eq!(
+            build_step_command_line(&config, &steps[2]),
+            Ok(CommandLine::Direct(PathBuf::from("echo"), vec![s!("direct")]))
+        );
+    }
+
+    #[test]
+    fn test_invalid_steps() {
+        let config = command(
+            "commands:
+    - comand: echo typo
+    - command: echo both
+      argv: [echo, both]",
+        );
+        let steps = steps(&config);
+
+        assert_eq!(
+            build_step_command_line(&config, &steps[0]),
+            Err(s!("a step needs a command, a script or argv"))
+        );
+        assert_eq!(
+            build_step_command_line(&config, &steps[1]),
+            Err(s!("a step can have a command or argv, not both"))
+        );
+    }
+
+    #[test]
+    fn test_step_limits() {
+        let config = command(
+            "timeout: 10m
+retries:
+    count: 3
+    delay: 2s
+commands:
+    - make
+    - command: make test
+      timeout: 30s
+      retries:
+          count: 2
+          backoff: exponential
+          delay: 1s",
+        );
+        let steps = steps(&config);
+
+        let limits = build_step_limits(&config, &steps[0]).ok().unwrap();
+        assert_eq!(limits.timeout, Some((s!("10m"), Duration::from_secs(600))));
+        let retries = limits.retries.unwrap();
+        assert_eq!(retries.delay_before(0), Duration::from_secs(2));
+        assert_eq!(retries.delay_before(2), Duration::from_secs(2));
+
+        let limits = build_step_limits(&config, &steps[1]).ok().unwrap();
+        assert_eq!(limits.timeout, Some((s!("30s"), Duration::from_secs(30))));
+        let retries = limits.retries.unwrap();
+        assert_eq!(retries.delay_before(0), Duration::from_secs(1));
+        assert_eq!(retries.delay_before(1), Duration::from_secs(2));
+        assert_eq!(retries.delay_before(3), Duration::from_secs(8));
+    }
+
+    #[test]
+    fn test_invalid_step_limits() {
+        let config = command(
+            "commands:
+    - command: make
+      timeout: forever
+    - command: make
+      retries:
+          count: 2
+          delay: later",
+        );
+        let steps = steps(&config);
+
+        let error = |step: &CommandAndEnv| match build_step_limits(&config, step) {
+            Ok(_) => s!(""),
+            Err(err) => err,
+        };
+        assert!(error(&steps[0]).starts_with("invalid timeout"));
+        assert!(error(&steps[1]).starts_with("invalid retry delay"));
+    }
+
+    #[test]
+    fn test_resolve_working_dir() {
+        let project_dir = Path::new("/work/project");
+        assert_eq!(
+            resolve_working_dir(Some(project_dir), "sub/dir"),
+            PathBuf::from("/work/project/sub/dir")
+        );
+        assert_eq!(resolve_working_dir(Some(project_dir), "/tmp"), PathBuf::from("/tmp"));
+        assert_eq!(resolve_working_dir(None, "sub/dir"), PathBuf::from("sub/dir"));
+    }
+
+    #[test]
+    fn test_safe_file_name() {
+        assert_eq!(safe_file_name("build"), "build");
+        assert_eq!(safe_file_name("test[os=linux,rust=1.30]"), "test_os_linux_rust_1.30_");
+        assert_eq!(safe_file_name("my-cmd_2"), "my-cmd_2");
+        assert_eq!(safe_file_name("../up"), ".._up");
+    }
+
+    #[test]
+    fn test_format_timestamp() {
+        let time: SystemTime = Utc.ymd(2018, 9, 30).and_hms_milli(14, 5, 9, 123).into();
+        assert_eq!(format_timestamp(time), "2018-09-30T14:05:09.123Z");
+    }
+}
@@ -0,0 +1,204 @@
+use inc_lib::core::config::{env_values, ReadyProbe, ServiceConfig, WaitFor};
+use inc_lib::exec::executor::{
+    start_background_process, BackgroundProcess, CliError, CommandLine, RunOptions,
+    RunningProcesses,
+};
+use inc_lib::exec::wait::{wait_until, Wait};
+use inc_lib::libs::duration::parse_duration;
+use inc_lib::libs::shell::build_command_line;
+use regex::Regex;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::mpsc::{channel, Receiver};
+use std::time::Duration;
+
+const DEFAULT_READY_TIMEOUT: &str = "30s";
+const DEFAULT_STOP_TIMEOUT: &str = "10s";
+
+/**
+ * How to start one of a command's services, and tell when it's ready.
+ */
+pub struct ServicePlan {
+    pub name: String,
+    command_line: CommandLine,
+    working_dir: Option<PathBuf>,
+    env: HashMap<String, String>,
+    probe: Option<Probe>,
+    ready_timeout: (String, Duration),
+    stop_timeout: Duration,
+}
+
+/**
+ * Everything but `log` is checked like a `wait_for` step.
+ */
+enum Probe {
+    Wait(Wait),
+    Log(Regex),
+}
+
+/**
+ * A service that was started, and is ready.
+ */
+pub struct RunningService {
+    pub name: String,
+    process: BackgroundProcess,
+    stop_timeout: Duration,
+}
+
+impl ServicePlan {
+    pub fn new(
+        config: &ServiceConfig,
+        shell: Option<&str>,
+        working_dir: Option<PathBuf>,
+    ) -> Result<Self, String> {
+        let command_line = build_command_line(shell, &config.command)?;
+        let nothing = WaitFor {
+            tcp: None,
+            file: None,
+            http: None,
+            command: None,
+            timeout: None,
+        };
+        let wait = |wait_for: WaitFor| -> Result<Option<Probe>, String> {
+            let working_dir = working_dir.as_ref().map(|x| x.as_path());
+            return Ok(Some(Probe::Wait(Wait::new(&wait_for, shell, working_dir)?)));
+        };
+        let probe = match config.ready {
+            Some(ReadyProbe::Tcp(port)) => wait(WaitFor {
+                tcp: Some(port.to_string()),
+                ..nothing
+            })?,
+            Some(ReadyProbe::Http(ref url)) => wait(WaitFor {
+                http: Some(url.clone()),
+                ..nothing
+            })?,
+            Some(ReadyProbe::Command(ref command)) => wait(WaitFor {
+                command: Some(command.clone()),
+                ..nothing
+            })?,
+            Some(ReadyProbe::Log(ref pattern)) => match Regex::new(pattern) {
+                Ok(regex) => Some(Probe::Log(regex)),
+                Err(err) => return Err(format!("invalid log pattern, {}", err)),
+            },
+            None => None,
+        };
+
+        let ready_timeout = config
+            .ready_timeout
+            .clone()
+            .unwrap_or(s!(DEFAULT_READY_TIMEOUT));
+        let stop_timeout = config
+            .stop_timeout
+            .clone()
+            .unwrap_or(s!(DEFAULT_STOP_TIMEOUT));
+
+        return Ok(ServicePlan {
+            name: config.name.clone(),
+            command_line: command_line,
+            working_dir: working_dir,
+            env: env_values(&config.env),
+            probe: probe,
+            ready_timeout: match parse_duration(&ready_timeout) {
+                Ok(duration) => (ready_timeout, duration),
+                Err(err) => return Err(format!("invalid ready_timeout, {}", err)),
+            },
+            stop_timeout: match parse_duration(&stop_timeout) {
+                Ok(duration) => duration,
+                Err(err) => return Err(format!("invalid stop_timeout, {}", err)),
+            },
+        });
+    }
+
+    pub fn describe(&self) -> String {
+        let probe = match self.probe {
+            Some(Probe::Wait(ref wait)) => wait.describe(),
+            Some(Probe::Log(ref regex)) => format!("the output to match `{}`", regex.as_str()),
+            None => s!("it to start"),
+        };
+
+        return format!("`{}`, waiting for {}", self.name, probe);
+    }
+
+    /**
+     * Starts the service, and waits for it to be ready. When it doesn't get ready, it's
+     * stopped again.
+     */
+    pub fn start(
+        &self,
+        extra_env: &HashMap<String, String>,
+        options: &RunOptions,
+        processes: &RunningProcesses,
+    ) -> Result<RunningService, String> {
+        let mut env = extra_env.clone();
+        env.extend(self.env.clone());
+        let mut options = options.clone();
+        options.working_dir = self.working_dir.clone();
+        options.prefix = Some(self.name.clone());
+
+        let (sender, receiver) = channel::<String>();
+        let lines = match self.probe {
+            Some(Probe::Log(_)) => Some(sender),
+            _ => None,
+        };
+
+        info!("*** Starting `{}`", self.name);
+        let mut process =
+            match start_background_process(&self.command_line, env.clone(), &options, lines) {
+                Ok(process) => process,
+                Err(err) => return Err(format!("Unable to start `{}`: {}", self.name, err.message)),
+            };
+
+        let ready = self.wait_until_ready(&mut process, &receiver, &env, &options, processes);
+        if let Err(err) = ready {
+            process.stop(self.stop_timeout);
+            return Err(err);
+        }
+
+        info!("*** `{}` is ready", self.name);
+        return Ok(RunningService {
+            name: self.name.clone(),
+            process: process,
+            stop_timeout: self.stop_timeout,
+        });
+    }
+
+    fn wait_until_ready(
+        &self,
+        process: &mut BackgroundProcess,
+        lines: &Receiver<String>,
+        env: &HashMap<String, String>,
+        options: &RunOptions,
+        processes: &RunningProcesses,
+    ) -> Result<(), String> {
+        let description = format!("`{}` to be ready", self.name);
+        let is_ready = |deadline| {
+            if let Some(exit_code) = process.exit_code() {
+                return Err(CliError::new(
+                    1,
+                    format!("`{}` exited with {} before it was ready", self.name, exit_code),
+                ));
+            }
+
+            return Ok(match self.probe {
+                Some(Probe::Wait(ref wait)) => wait.is_ready(env, options, processes, deadline),
+                Some(Probe::Log(ref regex)) => lines.try_iter().any(|x| regex.is_match(&x)),
+                None => true,
+            });
+        };
+
+        return wait_until(&description, &self.ready_timeout, processes, is_ready)
+            .map_err(|err| err.message);
+    }
+}
+
+impl RunningService {
+    pub fn stop(self) {
+        let mut process = self.process;
+        if let Some(exit_code) = process.exit_code() {
+            warn!("`{}` stopped on it's own, with {}", self.name, exit_code);
+        }
+
+        info!("*** Stopping `{}`", self.name);
+        process.stop(self.stop_timeout);
+    }
+}
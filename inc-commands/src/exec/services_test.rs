@@ -0,0 +1,69 @@
+#[cfg(test)]
+pub mod test {
+    use exec::services::*;
+    use inc_lib::core::config::ServiceConfig;
+    use serde_yaml;
+
+    fn plan(yaml: &str) -> Result<ServicePlan, String> {
+        let config = serde_yaml::from_str::<ServiceConfig>(yaml).unwrap();
+        return ServicePlan::new(&config, None, None);
+    }
+
+    fn describe(yaml: &str) -> String {
+        return match plan(yaml) {
+            Ok(plan) => plan.describe(),
+            Err(err) => panic!("{}", err),
+        };
+    }
+
+    fn error(yaml: &str) -> String {
+        return match plan(yaml) {
+            Ok(_) => s!(""),
+            Err(err) => err,
+        };
+    }
+
+    #[test]
+    fn test_describe() {
+        assert_eq!(
+            describe("{name: db, command: run-db, ready: {tcp: 5432}}"),
+            "`db`, waiting for localhost:5432 to accept connections"
+        );
+        assert_eq!(
+            describe("{name: api, command: run-api, ready: {http: 'http://localhost:8080/health'}}"),
+            "`api`, waiting for http://localhost:8080/health to return 200"
+        );
+        assert_eq!(
+            describe("{name: api, command: run-api, ready: {log: '^Listening'}}"),
+            "`api`, waiting for the output to match `^Listening`"
+        );
+        assert_eq!(
+            describe("{name: api, command: run-api, ready: {command: 'test -f ready'}}"),
+            "`api`, waiting for `test -f ready` to succeed"
+        );
+        assert_eq!(
+            describe("{name: api, command: run-api}"),
+            "`api`, waiting for it to start"
+        );
+    }
+
+    #[test]
+    fn test_invalid_services() {
+        assert!(
+            error("{name: api, command: run-api, ready: {http: 'https://localhost'}}")
+                .contains("only http:// URLs can be checked")
+        );
+        assert!(
+            error("{name: api, command: run-api, ready: {log: '(unclosed'}}")
+                .starts_with("invalid log pattern")
+        );
+        assert!(
+            error("{name: api, command: run-api, ready_timeout: soon}")
+                .starts_with("invalid ready_timeout")
+        );
+        assert!(
+            error("{name: api, command: run-api, stop_timeout: 10x}")
+                .starts_with("invalid stop_timeout")
+        );
+    }
+}
@@ -0,0 +1,251 @@
+use glob::{glob, Pattern};
+use inc_lib::exec::executor::{on_interrupt, CliError, CliResult, RunningProcesses};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{stdout, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{channel, RecvTimeoutError};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+use super::scheduler::{run_commands, ExecOptions, ScheduledCommand};
+
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+const DEBOUNCE: Duration = Duration::from_millis(300);
+const CANCEL_TIMEOUT: Duration = Duration::from_secs(5);
+
+pub type Snapshot = BTreeMap<PathBuf, (Option<SystemTime>, u64)>;
+
+pub struct WatchOptions {
+    pub project_dir: PathBuf,
+    pub patterns: Vec<String>,
+    pub clear: bool,
+}
+
+/**
+ * Runs the plan from `build_plan` every time a watched file changes, until inc is stopped.
+ * When no `patterns` are given, everything in the project that isn't in it's `.gitignore` is
+ * watched. A change while the plan is running interrupts it, and starts it again.
+ *
+ * A control-c while waiting for changes stops watching. While the plan is running, it cancels
+ * the run, and a second one stops watching right away.
+ */
+pub fn watch<F>(watch_options: WatchOptions, options: ExecOptions, build_plan: F) -> CliResult
+where
+    F: Fn() -> Result<Vec<ScheduledCommand>, CliError>,
+{
+    let ignores = read_gitignore(&watch_options.project_dir);
+    let take_snapshot = || -> Snapshot {
+        if watch_options.patterns.is_empty() {
+            let mut snapshot = Snapshot::new();
+            walk_project(&watch_options.project_dir, &watch_options.project_dir, &ignores, &mut snapshot);
+            return snapshot;
+        }
+
+        return snapshot_patterns(&watch_options.project_dir, &watch_options.patterns);
+    };
+
+    let interrupts = Arc::new(AtomicUsize::new(0));
+    let _interrupt_handler = {
+        let interrupts = interrupts.clone();
+        on_interrupt(move || {
+            interrupts.fetch_add(1, Ordering::SeqCst);
+        })
+    };
+
+    let mut snapshot = take_snapshot();
+    loop {
+        if watch_options.clear {
+            print!("\x1B[2J\x1B[H");
+            let _ = stdout().flush();
+        }
+
+        let processes = RunningProcesses::new();
+        let (sender, receiver) = channel::<CliResult>();
+        let mut running = match build_plan() {
+            Ok(plan) => {
+                let options = options.clone();
+                let processes = processes.clone();
+                thread::spawn(move || {
+                    let _ = sender.send(run_commands(plan, options, processes));
+                });
+                true
+            }
+            Err(err) => {
+                error!("{}", err.message);
+                info!("*** Waiting for changes");
+                false
+            }
+        };
+
+        let mut handled_interrupts = interrupts.load(Ordering::SeqCst);
+        loop {
+            if running {
+                match receiver.recv_timeout(POLL_INTERVAL) {
+                    Ok(result) => {
+                        running = false;
+                        report_result(result);
+                        handled_interrupts = interrupts.load(Ordering::SeqCst);
+                    }
+                    Err(RecvTimeoutError::Timeout) => {}
+                    Err(RecvTimeoutError::Disconnected) => running = false,
+                }
+            } else {
+                thread::sleep(POLL_INTERVAL);
+            }
+
+            // The first control-c of a run is handled by the run itself
+            let new_interrupts = interrupts.load(Ordering::SeqCst) - handled_interrupts;
+            if !running && new_interrupts > 0 {
+                return Ok(130);
+            }
+            if running && new_interrupts > 1 {
+                processes.kill_all();
+                return Ok(130);
+            }
+
+            let current = take_snapshot();
+            if current == snapshot {
+                continue;
+            }
+
+            snapshot = current;
+            loop {
+                thread::sleep(DEBOUNCE);
+                let current = take_snapshot();
+                if current == snapshot {
+                    break;
+                }
+                snapshot = current;
+            }
+
+            if running {
+                info!("*** Files changed, restarting");
+                processes.interrupt_all();
+                if let Err(RecvTimeoutError::Timeout) = receiver.recv_timeout(CANCEL_TIMEOUT) {
+                    processes.kill_all();
+                    let _ = receiver.recv();
+                }
+            } else {
+                info!("*** Files changed, running again");
+            }
+            break;
+        }
+    }
+}
+
+fn report_result(result: CliResult) {
+    match result {
+        Ok(0) => info!("*** Finished, waiting for changes"),
+        Ok(130) => info!("*** Cancelled, waiting for changes"),
+        Ok(code) => error!("*** Failed with {}, waiting for changes", code),
+        Err(err) => error!("*** {}, waiting for changes", err.message),
+    }
+}
+
+pub fn snapshot_patterns(project_dir: &Path, patterns: &[String]) -> Snapshot {
+    let mut snapshot = Snapshot::new();
+    for pattern in patterns.iter() {
+        let paths = match glob(&project_dir.join(pattern).to_string_lossy()) {
+            Ok(value) => value,
+            Err(err) => {
+                warn!("Invalid glob {}: {}", pattern, err);
+                continue;
+            }
+        };
+
+        for path in paths.filter_map(|x| x.ok()) {
+            add_file(&path, &mut snapshot);
+        }
+    }
+
+    return snapshot;
+}
+
+fn add_file(path: &Path, snapshot: &mut Snapshot) {
+    if let Ok(metadata) = fs::metadata(path) {
+        if metadata.is_file() {
+            snapshot.insert(
+                path.to_path_buf(),
+                (metadata.modified().ok(), metadata.len()),
+            );
+        }
+    }
+}
+
+/**
+ * A `.gitignore` pattern. Patterns without a `/` match a file or directory name anywhere,
+ * the others match the path from the project.
+ */
+pub struct IgnorePattern {
+    pattern: Pattern,
+    anchored: bool,
+    dir_only: bool,
+}
+
+/**
+ * Only the `.gitignore` at the top of the project is used, and negated patterns are skipped.
+ */
+pub fn read_gitignore(project_dir: &Path) -> Vec<IgnorePattern> {
+    let contents = match fs::read_to_string(project_dir.join(".gitignore")) {
+        Ok(value) => value,
+        Err(_) => return Vec::new(),
+    };
+
+    return contents
+        .lines()
+        .map(|x| x.trim())
+        .filter(|x| !x.is_empty() && !x.starts_with('#') && !x.starts_with('!'))
+        .filter_map(|line| {
+            let dir_only = line.ends_with('/');
+            let line = line.trim_end_matches('/');
+            let anchored = line.contains('/');
+            let line = line.trim_start_matches('/');
+            match Pattern::new(line) {
+                Ok(pattern) => Some(IgnorePattern {
+                    pattern: pattern,
+                    anchored: anchored,
+                    dir_only: dir_only,
+                }),
+                Err(_) => None,
+            }
+        }).collect();
+}
+
+pub fn walk_project(dir: &Path, project_dir: &Path, ignores: &[IgnorePattern], snapshot: &mut Snapshot) {
+    let entries = match fs::read_dir(dir) {
+        Ok(value) => value,
+        Err(_) => return,
+    };
+
+    for entry in entries.filter_map(|x| x.ok()) {
+        let path = entry.path();
+        let is_dir = path.is_dir();
+        let name = entry.file_name();
+        if name == ".git" || name == ".inc" {
+            continue;
+        }
+
+        let relative = path.strip_prefix(project_dir).unwrap_or(&path).to_path_buf();
+        let ignored = ignores.iter().any(|x| {
+            if x.dir_only && !is_dir {
+                return false;
+            }
+            if x.anchored {
+                return x.pattern.matches_path(&relative);
+            }
+            return x.pattern.matches(&name.to_string_lossy());
+        });
+        if ignored {
+            continue;
+        }
+
+        if is_dir {
+            walk_project(&path, project_dir, ignores, snapshot);
+        } else {
+            add_file(&path, snapshot);
+        }
+    }
+}
@@ -0,0 +1,92 @@
+#[cfg(test)]
+pub mod test {
+    use exec::watch::*;
+    use std::fs;
+    use std::path::{Path, PathBuf};
+
+    fn project_dir(name: &str) -> PathBuf {
+        let dir = ::std::env::temp_dir().join(format!(
+            "inc-watch-test-{}-{}",
+            name,
+            ::std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&dir);
+        let paths = [
+            "src/main.rs",
+            "src/debug.log",
+            "target/app",
+            "build/out",
+            "docs/build/index.html",
+            ".git/HEAD",
+            ".inc/fingerprints/build.txt",
+            "keep.log",
+        ];
+        for path in paths.iter() {
+            let path = dir.join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, "contents").unwrap();
+        }
+        return dir;
+    }
+
+    fn files(dir: &Path, snapshot: &Snapshot) -> Vec<String> {
+        return snapshot
+            .keys()
+            .map(|x| s!(x.strip_prefix(dir).unwrap().to_string_lossy()))
+            .collect();
+    }
+
+    #[test]
+    fn test_walk_project() {
+        let dir = project_dir("walk");
+        fs::write(
+            dir.join(".gitignore"),
+            "# Build output\ntarget/\n*.log\n/build\n!keep.log\n",
+        ).unwrap();
+
+        let mut snapshot = Snapshot::new();
+        walk_project(&dir, &dir, &read_gitignore(&dir), &mut snapshot);
+        assert_eq!(
+            files(&dir, &snapshot),
+            vec![".gitignore", "docs/build/index.html", "src/main.rs"]
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_walk_project_without_gitignore() {
+        let dir = project_dir("no-gitignore");
+        assert!(read_gitignore(&dir).is_empty());
+
+        let mut snapshot = Snapshot::new();
+        walk_project(&dir, &dir, &[], &mut snapshot);
+        assert_eq!(
+            files(&dir, &snapshot),
+            vec![
+                "build/out",
+                "docs/build/index.html",
+                "keep.log",
+                "src/debug.log",
+                "src/main.rs",
+                "target/app",
+            ]
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_snapshot_patterns() {
+        let dir = project_dir("patterns");
+        let patterns = vec![s!("src/*.rs"), s!("**/*.html"), s!("[invalid")];
+
+        let snapshot = snapshot_patterns(&dir, &patterns);
+        assert_eq!(files(&dir, &snapshot), vec!["docs/build/index.html", "src/main.rs"]);
+        assert_eq!(snapshot, snapshot_patterns(&dir, &patterns));
+
+        fs::write(dir.join("src/main.rs"), "fn main() { changed(); }").unwrap();
+        assert_ne!(snapshot, snapshot_patterns(&dir, &patterns));
+        fs::write(dir.join("src/lib.rs"), "").unwrap();
+        assert_eq!(snapshot_patterns(&dir, &patterns).len(), 3);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,8 +1,17 @@
+extern crate chrono;
 extern crate clap;
+extern crate glob;
 #[macro_use]
 extern crate inc_lib;
 #[macro_use]
 extern crate log;
+extern crate regex;
+extern crate serde;
+#[macro_use]
+extern crate serde_derive;
+#[cfg(test)]
+extern crate serde_yaml;
+extern crate sha2;
 
 pub mod checkout;
 pub mod exec;
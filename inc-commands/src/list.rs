@@ -1,31 +1,77 @@
-use clap::{App, ArgMatches, SubCommand};
+use clap::{App, Arg, ArgMatches, SubCommand};
 use inc_lib::core::command::AvaliableCommands;
 use inc_lib::core::config::ConfigContainer;
 use inc_lib::core::BASE_APPLICATION_NAME;
-use inc_lib::exec::executor::CliResult;
+use inc_lib::exec::executor::{CliError, CliResult};
+use inc_lib::libs::output::{serialize, OutputFormat};
 
 pub fn subcommand<'a, 'b>() -> App<'a, 'b> {
-    return SubCommand::with_name("list").about("List the known element for Inc.");
+    return SubCommand::with_name("list")
+        .about("List the known element for Inc.")
+        .arg(format_arg());
+}
+
+pub(crate) fn format_arg<'a, 'b>() -> Arg<'a, 'b> {
+    return Arg::with_name("format")
+        .long("format")
+        .help("How to write the list, text by default.")
+        .takes_value(true)
+        .possible_values(&["text", "json", "yaml"]);
+}
+
+#[derive(Serialize)]
+struct CommandList {
+    commands: Vec<CommandListing>,
+}
+
+#[derive(Serialize)]
+struct CommandListing {
+    name: String,
+    source: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    path: Option<String>,
 }
 
 pub fn execute(
-    _args: &ArgMatches,
+    args: &ArgMatches,
     avaliable_commands: AvaliableCommands,
     _config: ConfigContainer,
 ) -> CliResult {
-    let mut commands: Vec<String> = vec![s!("checkout"), s!("exec"), s!("list")];
+    let mut commands: Vec<CommandListing> = vec!["checkout", "exec", "list"]
+        .into_iter()
+        .map(|x| CommandListing {
+            name: s!(x),
+            source: "builtin",
+            path: None,
+        }).collect();
     avaliable_commands
         .find_commands_with_parent(BASE_APPLICATION_NAME)
         .into_iter()
         .for_each(|command| {
-            commands.push(format!(
-                "{} - External command from {:?}",
-                command.name(),
-                command.binary().path
-            ))
+            commands.push(CommandListing {
+                name: command.name(),
+                source: "external",
+                path: Some(s!(command.binary().path.to_string_lossy())),
+            })
         });
 
-    let commands: Vec<String> = commands.iter().map(|x| format!("  - {}", x)).collect();
+    let format = OutputFormat::from_name(args.value_of("format"));
+    if format != OutputFormat::Text {
+        return match serialize(&CommandList { commands: commands }, &format) {
+            Ok(output) => {
+                println!("{}", output);
+                Ok(0)
+            }
+            Err(err) => Err(CliError::new(1, err)),
+        };
+    }
+
+    let commands: Vec<String> = commands
+        .iter()
+        .map(|x| match x.path {
+            Some(ref path) => format!("  - {} - External command from {:?}", x.name, path),
+            None => format!("  - {}", x.name),
+        }).collect();
     info!("avaliable-commands:\n{}", commands.join("\n"));
     return Ok(0);
 }
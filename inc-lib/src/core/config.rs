@@ -1,9 +1,12 @@
 use dirs::home_dir;
+use libs::dotenv::parse_env_file;
+use libs::shell::join_args;
 use serde::de::DeserializeOwned;
 use serde_yaml;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::env::current_dir;
-use std::fs::File;
+use std::fmt;
+use std::fs::{read_to_string, File};
 use std::io::prelude::*;
 use std::io::Error as IoError;
 use std::path::PathBuf;
@@ -29,6 +32,8 @@ pub struct CheckoutConfigs {
 #[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
 #[serde(untagged)]
 pub enum Commands {
+    Script(ScriptCommand),
+    WaitFor(WaitForCommand),
     CommandAndEnv(CommandAndEnv),
     CommandList(String),
 }
@@ -36,10 +41,45 @@ pub enum Commands {
 impl Commands {
     pub fn to_command_and_envs(self) -> CommandAndEnv {
         return match self {
+            Commands::Script(script) => CommandAndEnv {
+                command: String::new(),
+                argv: Vec::new(),
+                command_env: script.command_env,
+                ignore_failures: script.ignore_failures,
+                shell: script.shell,
+                cwd: script.cwd,
+                when: script.when,
+                timeout: script.timeout,
+                retries: script.retries,
+                script: Some(script.script),
+                wait_for: None,
+            },
+            Commands::WaitFor(wait) => CommandAndEnv {
+                command: String::new(),
+                argv: Vec::new(),
+                command_env: wait.command_env,
+                ignore_failures: wait.ignore_failures,
+                shell: wait.shell,
+                cwd: wait.cwd,
+                when: wait.when,
+                timeout: None,
+                retries: wait.retries,
+                script: None,
+                wait_for: Some(wait.wait_for),
+            },
             Commands::CommandAndEnv(commands) => commands,
             Commands::CommandList(string) => CommandAndEnv {
                 command: string,
+                argv: Vec::new(),
                 command_env: HashMap::new(),
+                ignore_failures: None,
+                shell: None,
+                cwd: None,
+                when: None,
+                timeout: None,
+                retries: None,
+                script: None,
+                wait_for: None,
             },
         };
     }
@@ -51,22 +91,597 @@ pub struct ExecCommandConfig {
     pub ignore_failures: bool,
     #[serde(default = "default_description")]
     pub description: String,
+    #[serde(default)]
     #[serde(rename = "commands")]
     pub commands: Vec<Commands>,
+    /**
+     * Steps that run after `commands`, even when one of them failed or the run was cancelled.
+     */
+    #[serde(default)]
+    pub finally: Vec<Commands>,
+    /**
+     * Processes that are started, and ready, before the first step, and stopped after the last.
+     */
+    #[serde(default)]
+    pub services: Vec<ServiceConfig>,
+    #[serde(default)]
+    pub depends_on: Vec<String>,
+    #[serde(default)]
+    pub parallel: bool,
+    #[serde(default)]
+    pub shell: Option<String>,
+    #[serde(default)]
+    pub cwd: Option<String>,
+    #[serde(default)]
+    pub exit_code: ExitCodePolicy,
+    #[serde(default)]
+    pub env: HashMap<String, EnvValue>,
+    #[serde(default)]
+    pub env_file: Vec<String>,
+    /**
+     * Names of environment variables with values that are masked in the output.
+     */
+    #[serde(default)]
+    pub secrets: Vec<String>,
+    /**
+     * Env files where every value is secret.
+     */
+    #[serde(default)]
+    pub secrets_file: Vec<String>,
+    #[serde(default)]
+    pub when: Option<Condition>,
+    #[serde(default)]
+    pub inputs: Vec<String>,
+    #[serde(default)]
+    pub outputs: Vec<String>,
+    #[serde(default)]
+    pub watch: Vec<String>,
+    #[serde(default)]
+    pub timeout: Option<String>,
+    #[serde(default)]
+    pub retries: Option<RetryPolicy>,
+    #[serde(default)]
+    pub params: Vec<ParamConfig>,
+    #[serde(default)]
+    pub matrix: Option<MatrixConfig>,
+}
+
+/**
+ * Runs the steps once for every combination of the `values`, like
+ * `target: [x86_64-unknown-linux-gnu, x86_64-apple-darwin]`.
+ */
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+pub struct MatrixConfig {
+    /**
+     * Extra combinations, that are run after the others.
+     */
+    #[serde(default)]
+    pub include: Vec<BTreeMap<String, String>>,
+    /**
+     * Combinations that match all of the values of an entry aren't run.
+     */
+    #[serde(default)]
+    pub exclude: Vec<BTreeMap<String, String>>,
+    #[serde(flatten)]
+    pub values: BTreeMap<String, Vec<String>>,
+}
+
+impl MatrixConfig {
+    /**
+     * Every combination of the values, in the order they're written. The names are sorted, and
+     * the first one changes the slowest.
+     */
+    pub fn combinations(&self) -> Result<Vec<BTreeMap<String, String>>, String> {
+        let names = self
+            .values
+            .keys()
+            .chain(self.include.iter().flat_map(|x| x.keys()));
+        for name in names {
+            let valid = name.chars().all(|x| x.is_ascii_alphanumeric() || x == '_');
+            if name.is_empty() || !valid {
+                return Err(format!(
+                    "`{}` can't be used in a matrix, names can only have letters, numbers and `_`",
+                    name
+                ));
+            }
+        }
+
+        let mut combinations: Vec<BTreeMap<String, String>> = Vec::new();
+        if !self.values.is_empty() {
+            combinations.push(BTreeMap::new());
+        }
+        for (name, values) in self.values.iter() {
+            let mut expanded: Vec<BTreeMap<String, String>> = Vec::new();
+            for combination in combinations.iter() {
+                for value in values.iter() {
+                    let mut combination = combination.clone();
+                    combination.insert(name.clone(), value.clone());
+                    expanded.push(combination);
+                }
+            }
+            combinations = expanded;
+        }
+
+        combinations.retain(|combination| {
+            !self.exclude.iter().any(|exclude| {
+                exclude
+                    .iter()
+                    .all(|(name, value)| combination.get(name) == Some(value))
+            })
+        });
+        for include in self.include.iter() {
+            if !combinations.contains(include) {
+                combinations.push(include.clone());
+            }
+        }
+
+        return Ok(combinations);
+    }
+}
+
+/**
+ * A parameter that can be given to a command, after `--`.
+ */
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+pub struct ParamConfig {
+    pub name: String,
+    #[serde(rename = "type")]
+    #[serde(default)]
+    pub kind: ParamType,
+    /**
+     * The allowed values of an `enum`.
+     */
+    #[serde(default)]
+    pub values: Vec<String>,
+    #[serde(default)]
+    pub default: Option<ParamValue>,
+    #[serde(default)]
+    pub required: bool,
+    #[serde(default)]
+    pub help: Option<String>,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+pub enum ParamType {
+    #[serde(rename = "string")]
+    String,
+    #[serde(rename = "int")]
+    Int,
+    /**
+     * A flag, that's `true` when it's given.
+     */
+    #[serde(rename = "bool")]
+    Bool,
+    #[serde(rename = "enum")]
+    Enum,
+    /**
+     * A file or directory, relative to where inc was run.
+     */
+    #[serde(rename = "path")]
+    Path,
+}
+
+impl Default for ParamType {
+    fn default() -> Self {
+        return ParamType::String;
+    }
+}
+
+/**
+ * Lets a `default` be written as `3` or `true`, instead of only as a string.
+ */
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+#[serde(untagged)]
+pub enum ParamValue {
+    Bool(bool),
+    Int(i64),
+    Text(String),
+}
+
+impl fmt::Display for ParamValue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return match self {
+            ParamValue::Bool(value) => write!(f, "{}", value),
+            ParamValue::Int(value) => write!(f, "{}", value),
+            ParamValue::Text(value) => write!(f, "{}", value),
+        };
+    }
+}
+
+/**
+ * An `env` value, either a string or a `value` with `secret: true`, which masks it like the
+ * variables in `secrets`.
+ */
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+#[serde(untagged)]
+pub enum EnvValue {
+    Text(String),
+    Detailed {
+        value: String,
+        #[serde(default)]
+        secret: bool,
+    },
+}
+
+impl EnvValue {
+    pub fn value(&self) -> &str {
+        return match self {
+            EnvValue::Text(value) => value,
+            EnvValue::Detailed { value, .. } => value,
+        };
+    }
+
+    pub fn is_secret(&self) -> bool {
+        return match self {
+            EnvValue::Text(_) => false,
+            EnvValue::Detailed { secret, .. } => *secret,
+        };
+    }
+}
+
+impl From<String> for EnvValue {
+    fn from(value: String) -> Self {
+        return EnvValue::Text(value);
+    }
+}
+
+/**
+ * The values of an `env`, without the `secret` flags.
+ */
+pub fn env_values(env: &HashMap<String, EnvValue>) -> HashMap<String, String> {
+    return env
+        .iter()
+        .map(|(key, value)| (key.clone(), s!(value.value())))
+        .collect();
+}
+
+/**
+ * How often to run a failed step again, and how long to wait before each retry.
+ */
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+pub struct RetryPolicy {
+    pub count: u32,
+    #[serde(default)]
+    pub backoff: Backoff,
+    #[serde(default = "default_retry_delay")]
+    pub delay: String,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+pub enum Backoff {
+    /**
+     * Wait `delay` before every retry.
+     */
+    #[serde(rename = "constant")]
+    Constant,
+    /**
+     * Wait `delay`, then twice as long before every following retry.
+     */
+    #[serde(rename = "exponential")]
+    Exponential,
+}
+
+impl Default for Backoff {
+    fn default() -> Self {
+        return Backoff::Constant;
+    }
+}
+
+/**
+ * Decides if a command, or a step, should run. Anything that doesn't match is skipped, which
+ * isn't a failure.
+ */
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+pub enum Condition {
+    /**
+     * Matches the OS (`linux`, `macos` or `darwin`, `windows`, ...) or family (`unix`).
+     */
+    #[serde(rename = "os")]
+    Os(String),
+    /**
+     * `NAME` matches when the variable is set and not empty, `NAME=value` when it's `value`.
+     */
+    #[serde(rename = "env")]
+    Env(String),
+    #[serde(rename = "file_exists")]
+    FileExists(String),
+    #[serde(rename = "command_succeeds")]
+    CommandSucceeds(String),
+    #[serde(rename = "all")]
+    All(Vec<Condition>),
+    #[serde(rename = "any")]
+    Any(Vec<Condition>),
+    #[serde(rename = "not")]
+    Not(Box<Condition>),
+}
+
+/**
+ * A process that runs in the background while a command's steps run, like a mock server.
+ */
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+pub struct ServiceConfig {
+    pub name: String,
+    pub command: String,
+    #[serde(default)]
+    pub cwd: Option<String>,
+    #[serde(default)]
+    pub env: HashMap<String, EnvValue>,
+    #[serde(default)]
+    pub ready: Option<ReadyProbe>,
+    /**
+     * How long `ready` has to pass, 30s by default.
+     */
+    #[serde(default)]
+    pub ready_timeout: Option<String>,
+    /**
+     * How long the service has to stop after a SIGTERM, before it's killed, 10s by default.
+     */
+    #[serde(default)]
+    pub stop_timeout: Option<String>,
+}
+
+/**
+ * How to tell that a service is ready. It's checked until it passes, the service exits, or
+ * `ready_timeout` has passed.
+ */
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+pub enum ReadyProbe {
+    /**
+     * The port accepts connections on localhost.
+     */
+    #[serde(rename = "tcp")]
+    Tcp(u16),
+    /**
+     * A GET of the URL, like `http://localhost:8080/health`, returns 200.
+     */
+    #[serde(rename = "http")]
+    Http(String),
+    /**
+     * A line of the service's output matches the regex.
+     */
+    #[serde(rename = "log")]
+    Log(String),
+    /**
+     * The command, run like a step, succeeds.
+     */
+    #[serde(rename = "command")]
+    Command(String),
+}
+
+/**
+ * What to return once all the steps have run, when some failures were ignored. Ignored
+ * failures don't fail the command, unless it's `fail-if-any`.
+ */
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+pub enum ExitCodePolicy {
+    #[serde(rename = "fail-if-any")]
+    FailIfAny,
+    #[serde(rename = "always-succeed")]
+    AlwaysSucceed,
+}
+
+impl Default for ExitCodePolicy {
+    fn default() -> Self {
+        return ExitCodePolicy::AlwaysSucceed;
+    }
 }
 
 #[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
 pub struct ProjectConfig {
+    #[serde(default)]
     pub exec: HashMap<String, ExecCommandConfig>,
+    #[serde(default)]
+    pub shell: Option<String>,
+    #[serde(default)]
+    pub default_cwd: Option<DefaultWorkingDir>,
+    #[serde(default)]
+    pub env: HashMap<String, EnvValue>,
+    #[serde(default)]
+    pub env_file: Vec<String>,
+    #[serde(default)]
+    pub secrets: Vec<String>,
+    #[serde(default)]
+    pub secrets_file: Vec<String>,
+    /**
+     * Where `inc exec` writes the output of every step, relative to this file, unless
+     * `--log-dir` is given.
+     */
+    #[serde(default)]
+    pub log_dir: Option<String>,
+}
+
+/**
+ * Where commands without a `cwd` run.
+ */
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+pub enum DefaultWorkingDir {
+    #[serde(rename = "current")]
+    Current,
+    #[serde(rename = "project")]
+    Project,
 }
 
 #[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
 pub struct CommandAndEnv {
+    #[serde(default)]
     pub command: String,
 
+    #[serde(default)]
+    pub argv: Vec<String>,
+
+    #[serde(default)]
+    #[serde(rename = "env")]
+    pub command_env: HashMap<String, EnvValue>,
+
+    #[serde(default)]
+    pub ignore_failures: Option<bool>,
+
+    #[serde(default)]
+    pub shell: Option<String>,
+
+    #[serde(default)]
+    pub cwd: Option<String>,
+
+    #[serde(default)]
+    pub when: Option<Condition>,
+
+    #[serde(default)]
+    pub timeout: Option<String>,
+
+    #[serde(default)]
+    pub retries: Option<RetryPolicy>,
+
+    /**
+     * Set when the step came from a `script`. The `argv` are then passed to the script.
+     */
+    #[serde(skip)]
+    pub script: Option<String>,
+
+    /**
+     * Set when the step came from a `wait_for`, which is checked by inc instead of being run.
+     */
+    #[serde(skip)]
+    pub wait_for: Option<WaitFor>,
+}
+
+/**
+ * A multi-line script, which is run as a single file so shell state carries between lines.
+ */
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+pub struct ScriptCommand {
+    pub script: String,
+
+    #[serde(default)]
+    #[serde(rename = "env")]
+    pub command_env: HashMap<String, EnvValue>,
+
+    #[serde(default)]
+    pub ignore_failures: Option<bool>,
+
+    #[serde(default)]
+    pub shell: Option<String>,
+
+    #[serde(default)]
+    pub cwd: Option<String>,
+
+    #[serde(default)]
+    pub when: Option<Condition>,
+
+    #[serde(default)]
+    pub timeout: Option<String>,
+
+    #[serde(default)]
+    pub retries: Option<RetryPolicy>,
+}
+
+/**
+ * A step that waits until something is ready, like a port accepting connections. It works
+ * without `nc` or `curl` being installed.
+ */
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+pub struct WaitForCommand {
+    pub wait_for: WaitFor,
+
     #[serde(default)]
     #[serde(rename = "env")]
-    pub command_env: HashMap<String, String>,
+    pub command_env: HashMap<String, EnvValue>,
+
+    #[serde(default)]
+    pub ignore_failures: Option<bool>,
+
+    #[serde(default)]
+    pub shell: Option<String>,
+
+    #[serde(default)]
+    pub cwd: Option<String>,
+
+    #[serde(default)]
+    pub when: Option<Condition>,
+
+    #[serde(default)]
+    pub retries: Option<RetryPolicy>,
+}
+
+/**
+ * What a `wait_for` step waits for. Exactly one of `tcp`, `file`, `http` and `command` has to
+ * be set.
+ */
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+pub struct WaitFor {
+    /**
+     * A `host:port` that accepts connections, or just a port on localhost.
+     */
+    #[serde(default)]
+    pub tcp: Option<String>,
+
+    /**
+     * A path that exists, relative to the step's `cwd`.
+     */
+    #[serde(default)]
+    pub file: Option<String>,
+
+    /**
+     * A URL that returns 200 to a GET.
+     */
+    #[serde(default)]
+    pub http: Option<String>,
+
+    /**
+     * A command, run like a step, that succeeds.
+     */
+    #[serde(default)]
+    pub command: Option<String>,
+
+    /**
+     * How long to wait, 30s by default.
+     */
+    #[serde(default)]
+    pub timeout: Option<String>,
+}
+
+impl WaitFor {
+    /**
+     * Like `tcp localhost:5432`.
+     */
+    pub fn display(&self) -> String {
+        let targets = vec![
+            ("tcp", &self.tcp),
+            ("file", &self.file),
+            ("http", &self.http),
+            ("command", &self.command),
+        ];
+        let targets: Vec<String> = targets
+            .into_iter()
+            .filter_map(|(kind, target)| target.as_ref().map(|x| format!("{} {}", kind, x)))
+            .collect();
+        return targets.join(", ");
+    }
+}
+
+impl CommandAndEnv {
+    /**
+     * The step as it would be typed into a shell, for telling the user what is running.
+     */
+    pub fn display_command(&self) -> String {
+        if let Some(ref wait_for) = self.wait_for {
+            return format!("wait for {}", wait_for.display());
+        }
+
+        if let Some(ref script) = self.script {
+            let mut lines = script.lines().filter(|x| !x.trim().is_empty());
+            let first_line = lines.next().unwrap_or("").trim();
+            if lines.next().is_some() {
+                return format!("{} ...", first_line);
+            }
+            return s!(first_line);
+        }
+
+        if self.argv.is_empty() {
+            return self.command.clone();
+        }
+
+        return join_args(&self.argv);
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -96,12 +711,84 @@ impl<T> ConfigWithPath<T> {
 pub struct ExecConfig {
     pub commands: HashMap<String, ExecCommandConfig>,
     pub command_defintions: HashMap<String, PathBuf>,
+    /**
+     * The `log_dir` from the closest `inc.yaml` that has one.
+     */
+    pub log_dir: Option<PathBuf>,
+}
+
+impl ExecConfig {
+    /**
+     * Computes the commands that need to run for `names`, in the order they're given, with
+     * dependencies first. Every command shows up once, even when multiple commands depend
+     * on it.
+     */
+    pub fn resolve_dependencies(&self, names: &[&str]) -> Result<Vec<String>, String> {
+        let mut order: Vec<String> = Vec::new();
+        let mut path: Vec<String> = Vec::new();
+
+        for name in names.iter() {
+            if let Err(err) = self.visit_dependency(name, &mut path, &mut order) {
+                return Err(err);
+            }
+        }
+
+        return Ok(order);
+    }
+
+    fn visit_dependency(
+        &self,
+        name: &str,
+        path: &mut Vec<String>,
+        order: &mut Vec<String>,
+    ) -> Result<(), String> {
+        if order.iter().any(|x| x == name) {
+            return Ok(());
+        }
+
+        if let Some(position) = path.iter().position(|x| x == name) {
+            let mut cycle: Vec<String> = path[position..].to_vec();
+            cycle.push(s!(name));
+            return Err(format!(
+                "Found a dependency cycle: {}! Failing!",
+                cycle.join(" -> ")
+            ));
+        }
+
+        let config = match self.commands.get(name) {
+            Some(value) => value,
+            None => {
+                return Err(match path.last() {
+                    Some(parent) => format!(
+                        "Unable to find command list for {}, which {} depends on! Failing!",
+                        name, parent
+                    ),
+                    None => format!("Unable to find command list for {}! Failing!", name),
+                });
+            }
+        };
+
+        path.push(s!(name));
+        for dependency in config.depends_on.iter() {
+            if let Err(err) = self.visit_dependency(dependency, path, order) {
+                return Err(err);
+            }
+        }
+        path.pop();
+
+        order.push(s!(name));
+        return Ok(());
+    }
 }
 
 fn default_description() -> String {
     return s!("No Description Provided");
 }
 
+fn default_retry_delay() -> String {
+    return s!("1s");
+}
+
 fn default_ignore_failures() -> bool {
     return false;
 }
@@ -127,7 +814,14 @@ impl ConfigContainer {
             }),
         };
 
-        trace!("Project Configs Found: {:?}", project_config);
+        // Just the files, the configs themselves can have secrets in them
+        trace!(
+            "Project Configs Found: {:?}",
+            project_config
+                .iter()
+                .map(|x| x.file.clone())
+                .collect::<Vec<Option<PathBuf>>>()
+        );
         trace!("Home Configs Found: {:?}", home_configs);
         return Ok(ConfigContainer {
             project_config: project_config,
@@ -135,13 +829,77 @@ impl ConfigContainer {
         });
     }
 
-    pub fn get_exec_configs(&self) -> ExecConfig {
+    /**
+     * Collects the commands from every `inc.yaml`, the closest one winning. Each command's `env`
+     * ends up with everything it inherits, in order of precedence: the `inc.yaml` files above
+     * it, then the one that defined it, then the command itself. `env_file`s, then
+     * `secrets_file`s, are loaded before the `env` next to them, so `env` wins. `secrets` are
+     * inherited the same way, and include everything from a `secrets_file`.
+     */
+    pub fn get_exec_configs(&self) -> Result<ExecConfig, String> {
         let mut command_map: HashMap<String, ExecCommandConfig> = HashMap::new();
         let mut command_defintion_map: HashMap<String, PathBuf> = HashMap::new();
 
-        for project_config in self.project_config.clone().into_iter() {
-            for (key, value) in project_config.config.exec.into_iter() {
+        for (i, project_config) in self.project_config.clone().into_iter().enumerate() {
+            let default_shell = self.project_config[i..]
+                .iter()
+                .filter_map(|x| x.config.shell.clone())
+                .next();
+            let default_cwd = self.project_config[i..]
+                .iter()
+                .filter_map(|x| x.config.default_cwd.clone())
+                .next();
+
+            let mut project_env: HashMap<String, EnvValue> = HashMap::new();
+            let mut project_secrets: Vec<String> = Vec::new();
+            for parent in self.project_config[i..].iter().rev() {
+                if let Err(err) = load_env_files(&parent.config.env_file, &parent.file, &mut project_env) {
+                    return Err(err);
+                }
+                if let Err(err) = load_secrets_files(
+                    &parent.config.secrets_file,
+                    &parent.file,
+                    &mut project_env,
+                    &mut project_secrets,
+                ) {
+                    return Err(err);
+                }
+                project_env.extend(parent.config.env.clone());
+                project_secrets.extend(parent.config.secrets.clone());
+            }
+
+            for (key, mut value) in project_config.config.exec.into_iter() {
                 if !command_map.contains_key(&key) {
+                    if value.shell.is_none() {
+                        value.shell = default_shell.clone();
+                    }
+                    if value.cwd.is_none() && default_cwd == Some(DefaultWorkingDir::Project) {
+                        value.cwd = Some(s!("."));
+                    }
+
+                    let mut env = project_env.clone();
+                    if let Err(err) = load_env_files(&value.env_file, &project_config.file, &mut env) {
+                        return Err(err);
+                    }
+                    let mut secrets = project_secrets.clone();
+                    if let Err(err) = load_secrets_files(
+                        &value.secrets_file,
+                        &project_config.file,
+                        &mut env,
+                        &mut secrets,
+                    ) {
+                        return Err(err);
+                    }
+                    env.extend(value.env);
+                    value.env = env;
+                    value.env_file = Vec::new();
+                    secrets.extend(value.secrets.clone());
+                    secrets.extend(secret_env(&value));
+                    secrets.sort();
+                    secrets.dedup();
+                    value.secrets = secrets;
+                    value.secrets_file = Vec::new();
+
                     command_map.insert(key.clone(), value);
 
                     if let Some(file) = project_config.file.clone() {
@@ -151,10 +909,23 @@ impl ConfigContainer {
             }
         }
 
-        return ExecConfig {
+        let log_dir = self
+            .project_config
+            .iter()
+            .filter_map(|x| {
+                x.config.log_dir.as_ref().map(|dir| {
+                    match x.file.as_ref().and_then(|file| file.parent()) {
+                        Some(project_dir) => project_dir.join(dir),
+                        None => PathBuf::from(dir),
+                    }
+                })
+            }).next();
+
+        return Ok(ExecConfig {
             commands: command_map,
             command_defintions: command_defintion_map,
-        };
+            log_dir: log_dir,
+        });
     }
 
     pub fn get_home_configs(&self) -> HomeConfig {
@@ -162,6 +933,78 @@ impl ConfigContainer {
     }
 }
 
+/**
+ * Relative paths are from the directory of the `inc.yaml` that listed the file.
+ */
+fn load_env_files(
+    env_files: &[String],
+    defined_in: &Option<PathBuf>,
+    env: &mut HashMap<String, EnvValue>,
+) -> Result<(), String> {
+    for env_file in env_files.iter() {
+        let path = match defined_in.as_ref().and_then(|x| x.parent()) {
+            Some(dir) => dir.join(env_file),
+            None => PathBuf::from(env_file),
+        };
+
+        let contents = match read_to_string(&path) {
+            Ok(value) => value,
+            Err(err) => return Err(format!("Unable to read env file {:?}: {}! Failing!", path, err)),
+        };
+
+        match parse_env_file(&contents) {
+            Ok(values) => env.extend(
+                values
+                    .into_iter()
+                    .map(|(key, value)| (key, EnvValue::from(value))),
+            ),
+            Err(err) => return Err(format!("Unable to parse env file {:?}: {}! Failing!", path, err)),
+        }
+    }
+
+    return Ok(());
+}
+
+/**
+ * Like `load_env_files`, also adding the names of everything that was loaded to `secrets`.
+ */
+fn load_secrets_files(
+    secrets_files: &[String],
+    defined_in: &Option<PathBuf>,
+    env: &mut HashMap<String, EnvValue>,
+    secrets: &mut Vec<String>,
+) -> Result<(), String> {
+    let mut loaded: HashMap<String, EnvValue> = HashMap::new();
+    load_env_files(secrets_files, defined_in, &mut loaded)?;
+    secrets.extend(loaded.keys().cloned());
+    env.extend(loaded);
+
+    return Ok(());
+}
+
+/**
+ * Names of the variables marked `secret: true`, in the command's `env`, or the `env` of any
+ * of it's steps and services.
+ */
+fn secret_env(config: &ExecCommandConfig) -> Vec<String> {
+    let steps = config
+        .commands
+        .iter()
+        .chain(config.finally.iter())
+        .map(|x| x.clone().to_command_and_envs().command_env);
+    let services = config.services.iter().map(|x| x.env.clone());
+
+    return steps
+        .chain(services)
+        .chain(vec![config.env.clone()])
+        .flat_map(|env| {
+            env.into_iter()
+                .filter(|(_, value)| value.is_secret())
+                .map(|(key, _)| key)
+                .collect::<Vec<String>>()
+        }).collect();
+}
+
 fn collapse_the_configs<T>(config_files: Vec<PathBuf>) -> Result<Vec<ConfigWithPath<T>>, String>
 where
     T: DeserializeOwned,
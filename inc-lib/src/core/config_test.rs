@@ -67,7 +67,7 @@ pub mod test {
             }),
         };
 
-        let exec_configs = config_container.get_exec_configs();
+        let exec_configs = config_container.get_exec_configs().unwrap();
 
         assert!(exec_configs.commands.contains_key("foo"), "has foo key");
         assert!(exec_configs.commands.contains_key("bar"), "has foo bar");
@@ -111,4 +111,516 @@ pub mod test {
             Some(&Commands::CommandList(String::from("flig3")))
         );
     }
+
+    #[test]
+    fn test_ignore_failures_on_steps() {
+        let foo_commands = "exec:
+    foo:
+        ignore_failures: true
+        exit_code: fail-if-any
+        commands: 
+            - command: bar
+              ignore_failures: false
+            - baz
+    bar:
+        ignore_failures: true
+        commands: 
+            - baz";
+        let result = serde_yaml::from_str::<ProjectConfig>(foo_commands).unwrap();
+        let foo = result.exec.get("foo").unwrap();
+        assert_eq!(foo.ignore_failures, true);
+        assert_eq!(foo.exit_code, ExitCodePolicy::FailIfAny);
+        assert_eq!(
+            result.exec.get("bar").unwrap().exit_code,
+            ExitCodePolicy::AlwaysSucceed
+        );
+
+        let commands: Vec<CommandAndEnv> = foo
+            .clone()
+            .commands
+            .into_iter()
+            .map(|x| x.to_command_and_envs())
+            .collect();
+        assert_eq!(commands[0].ignore_failures, Some(false));
+        assert_eq!(commands[1].ignore_failures, None);
+    }
+
+    fn build_exec_config(yaml: &str) -> ExecConfig {
+        let yaml = serde_yaml::from_str::<ProjectConfig>(yaml).unwrap();
+        let config_container = ConfigContainer {
+            project_config: vec![ConfigWithPath::no_file(yaml)],
+            home_config: ConfigWithPath::no_file(HomeConfig {
+                checkout: CheckoutConfigs {
+                    default_provider: None,
+                },
+            }),
+        };
+
+        return config_container.get_exec_configs().unwrap();
+    }
+
+    #[test]
+    fn test_dependencies_run_once_and_first() {
+        let exec_configs = build_exec_config(
+            "exec:
+    build:
+        commands: 
+            - bar
+    lint:
+        depends_on: [build]
+        commands: 
+            - bar
+    test:
+        depends_on: [build]
+        commands: 
+            - bar
+    ci:
+        depends_on: [lint, test]",
+        );
+
+        assert_eq!(
+            exec_configs.resolve_dependencies(&["ci"]),
+            Ok(vec![s!("build"), s!("lint"), s!("test"), s!("ci")])
+        );
+        assert_eq!(
+            exec_configs.resolve_dependencies(&["build"]),
+            Ok(vec![s!("build")])
+        );
+        assert_eq!(
+            exec_configs.resolve_dependencies(&["test", "lint", "build"]),
+            Ok(vec![s!("build"), s!("test"), s!("lint")])
+        );
+    }
+
+    #[test]
+    fn test_dependency_cycles_are_named() {
+        let exec_configs = build_exec_config(
+            "exec:
+    a:
+        depends_on: [b]
+    b:
+        depends_on: [c]
+    c:
+        depends_on: [b]",
+        );
+
+        assert_eq!(
+            exec_configs.resolve_dependencies(&["a"]),
+            Err(s!("Found a dependency cycle: b -> c -> b! Failing!"))
+        );
+    }
+
+    #[test]
+    fn test_missing_dependency() {
+        let exec_configs = build_exec_config(
+            "exec:
+    a:
+        depends_on: [b]",
+        );
+
+        assert_eq!(
+            exec_configs.resolve_dependencies(&["a"]),
+            Err(s!(
+                "Unable to find command list for b, which a depends on! Failing!"
+            ))
+        );
+    }
+
+    #[test]
+    fn test_shell_defaults_to_defining_file() {
+        let child = "exec:
+    foo:
+        commands: 
+            - bar
+    baz:
+        shell: zsh
+        commands: 
+            - bar";
+        let parent = "shell: bash
+exec:
+    flig:
+        commands: 
+            - bar";
+        let root = "shell: sh
+exec:
+    root:
+        commands: 
+            - bar";
+
+        let config_container = ConfigContainer {
+            project_config: vec![
+                ConfigWithPath::no_file(serde_yaml::from_str::<ProjectConfig>(child).unwrap()),
+                ConfigWithPath::no_file(serde_yaml::from_str::<ProjectConfig>(parent).unwrap()),
+                ConfigWithPath::no_file(serde_yaml::from_str::<ProjectConfig>(root).unwrap()),
+            ],
+            home_config: ConfigWithPath::no_file(HomeConfig {
+                checkout: CheckoutConfigs {
+                    default_provider: None,
+                },
+            }),
+        };
+
+        let exec_configs = config_container.get_exec_configs().unwrap();
+        let shell_for = |name: &str| exec_configs.commands.get(name).unwrap().shell.clone();
+        assert_eq!(shell_for("foo"), Some(s!("bash")));
+        assert_eq!(shell_for("baz"), Some(s!("zsh")));
+        assert_eq!(shell_for("flig"), Some(s!("bash")));
+        assert_eq!(shell_for("root"), Some(s!("sh")));
+    }
+
+    #[test]
+    fn test_script_commands() {
+        let foo_commands = "exec:
+    foo:
+        commands: 
+            - script: |
+                cd bar
+                baz
+              env:
+                FLIG: flag
+            - command: bar";
+        let result = serde_yaml::from_str::<ProjectConfig>(foo_commands).unwrap();
+        let foo = result.exec.get("foo").unwrap();
+
+        let commands: Vec<CommandAndEnv> = foo
+            .clone()
+            .commands
+            .into_iter()
+            .map(|x| x.to_command_and_envs())
+            .collect();
+        assert_eq!(commands[0].script, Some(s!("cd bar\nbaz\n")));
+        assert_eq!(commands[0].command_env.get("FLIG"), Some(&EnvValue::from(s!("flag"))));
+        assert_eq!(commands[0].display_command(), "cd bar ...");
+        assert_eq!(commands[1].script, None);
+        assert_eq!(commands[1].command, "bar");
+    }
+
+    #[test]
+    fn test_default_cwd_from_project() {
+        let exec_configs = build_exec_config(
+            "default_cwd: project
+exec:
+    foo:
+        commands: 
+            - bar
+    baz:
+        cwd: sub
+        commands: 
+            - bar",
+        );
+
+        assert_eq!(
+            exec_configs.commands.get("foo").unwrap().cwd,
+            Some(s!("."))
+        );
+        assert_eq!(
+            exec_configs.commands.get("baz").unwrap().cwd,
+            Some(s!("sub"))
+        );
+
+        let exec_configs = build_exec_config(
+            "exec:
+    foo:
+        commands: 
+            - bar",
+        );
+        assert_eq!(exec_configs.commands.get("foo").unwrap().cwd, None);
+    }
+
+    #[test]
+    fn test_env_is_layered() {
+        let child = "env:
+    CHILD: child
+    SHARED: child
+exec:
+    foo:
+        env:
+            SHARED: foo
+        commands: 
+            - bar
+    baz:
+        commands: 
+            - bar";
+        let parent = "env:
+    PARENT: parent
+    SHARED: parent
+exec:
+    root:
+        commands: 
+            - bar";
+
+        let config_container = ConfigContainer {
+            project_config: vec![
+                ConfigWithPath::no_file(serde_yaml::from_str::<ProjectConfig>(child).unwrap()),
+                ConfigWithPath::no_file(serde_yaml::from_str::<ProjectConfig>(parent).unwrap()),
+            ],
+            home_config: ConfigWithPath::no_file(HomeConfig {
+                checkout: CheckoutConfigs {
+                    default_provider: None,
+                },
+            }),
+        };
+
+        let exec_configs = config_container.get_exec_configs().unwrap();
+        let env_for = |name: &str, key: &str| {
+            exec_configs.commands.get(name).unwrap().env.get(key).map(|x| s!(x.value()))
+        };
+        assert_eq!(env_for("foo", "SHARED"), Some(s!("foo")));
+        assert_eq!(env_for("foo", "PARENT"), Some(s!("parent")));
+        assert_eq!(env_for("baz", "SHARED"), Some(s!("child")));
+        assert_eq!(env_for("baz", "CHILD"), Some(s!("child")));
+        assert_eq!(env_for("root", "SHARED"), Some(s!("parent")));
+        assert_eq!(env_for("root", "CHILD"), None);
+    }
+
+    #[test]
+    fn test_secrets_are_inherited() {
+        let child = "secrets: [CHILD_TOKEN]
+exec:
+    foo:
+        secrets: [FOO_TOKEN, CHILD_TOKEN]
+        commands: 
+            - bar
+    baz:
+        commands: 
+            - bar";
+        let parent = "secrets: [PARENT_TOKEN]
+exec:
+    root:
+        commands: 
+            - bar";
+
+        let config_container = ConfigContainer {
+            project_config: vec![
+                ConfigWithPath::no_file(serde_yaml::from_str::<ProjectConfig>(child).unwrap()),
+                ConfigWithPath::no_file(serde_yaml::from_str::<ProjectConfig>(parent).unwrap()),
+            ],
+            home_config: ConfigWithPath::no_file(HomeConfig {
+                checkout: CheckoutConfigs {
+                    default_provider: None,
+                },
+            }),
+        };
+
+        let exec_configs = config_container.get_exec_configs().unwrap();
+        let secrets_for = |name: &str| exec_configs.commands.get(name).unwrap().secrets.clone();
+        assert_eq!(
+            secrets_for("foo"),
+            vec![s!("CHILD_TOKEN"), s!("FOO_TOKEN"), s!("PARENT_TOKEN")]
+        );
+        assert_eq!(secrets_for("baz"), vec![s!("CHILD_TOKEN"), s!("PARENT_TOKEN")]);
+        assert_eq!(secrets_for("root"), vec![s!("PARENT_TOKEN")]);
+    }
+
+    #[test]
+    fn test_secret_env() {
+        let yaml = "env:
+    PARENT_TOKEN:
+        value: parent
+        secret: true
+exec:
+    foo:
+        env:
+            PLAIN: plain
+            FOO_TOKEN: { value: foo, secret: true }
+        commands: 
+            - command: bar
+              env:
+                  STEP_TOKEN: { value: step, secret: true }
+                  NOT_SECRET: { value: step }
+        services:
+            - name: db
+              command: run-db
+              env:
+                  DB_TOKEN: { value: db, secret: true }";
+
+        let config_container = ConfigContainer {
+            project_config: vec![ConfigWithPath::no_file(
+                serde_yaml::from_str::<ProjectConfig>(yaml).unwrap(),
+            )],
+            home_config: ConfigWithPath::no_file(HomeConfig {
+                checkout: CheckoutConfigs {
+                    default_provider: None,
+                },
+            }),
+        };
+
+        let exec_configs = config_container.get_exec_configs().unwrap();
+        let foo = exec_configs.commands.get("foo").unwrap();
+        assert_eq!(
+            foo.secrets,
+            vec![s!("DB_TOKEN"), s!("FOO_TOKEN"), s!("PARENT_TOKEN"), s!("STEP_TOKEN")]
+        );
+        assert_eq!(foo.env.get("FOO_TOKEN").map(|x| x.value()), Some("foo"));
+        assert_eq!(foo.env.get("PLAIN").map(|x| x.value()), Some("plain"));
+    }
+
+    #[test]
+    fn test_services() {
+        let yaml = serde_yaml::from_str::<ProjectConfig>(
+            "exec:
+    foo:
+        services:
+            - name: db
+              command: run-db
+              ready:
+                  tcp: 5432
+              ready_timeout: 1m
+            - name: api
+              command: run-api
+        commands: 
+            - bar",
+        ).unwrap();
+
+        let services = &yaml.exec.get("foo").unwrap().services;
+        assert_eq!(services.len(), 2);
+        assert_eq!(services[0].name, "db");
+        assert_eq!(services[0].ready, Some(ReadyProbe::Tcp(5432)));
+        assert_eq!(services[0].ready_timeout, Some(s!("1m")));
+        assert_eq!(services[1].ready, None);
+        assert_eq!(services[1].stop_timeout, None);
+    }
+
+    #[test]
+    fn test_missing_env_file() {
+        let yaml = serde_yaml::from_str::<ProjectConfig>(
+            "env_file: [does-not-exist.env]
+exec:
+    foo:
+        commands: 
+            - bar",
+        ).unwrap();
+        let config_container = ConfigContainer {
+            project_config: vec![ConfigWithPath::no_file(yaml)],
+            home_config: ConfigWithPath::no_file(HomeConfig {
+                checkout: CheckoutConfigs {
+                    default_provider: None,
+                },
+            }),
+        };
+
+        let err = config_container.get_exec_configs().unwrap_err();
+        assert!(err.contains("does-not-exist.env"), "{}", err);
+    }
+
+    #[test]
+    fn test_when_conditions() {
+        let foo_commands = "exec:
+    foo:
+        when:
+            os: linux
+        commands: 
+            - command: bar
+              when:
+                  all:
+                      - env: CI=true
+                      - not:
+                          file_exists: Cargo.lock
+                      - any:
+                          - command_succeeds: which docker";
+        let result = serde_yaml::from_str::<ProjectConfig>(foo_commands).unwrap();
+        let foo = result.exec.get("foo").unwrap();
+        assert_eq!(foo.when, Some(Condition::Os(s!("linux"))));
+
+        let step = foo.commands[0].clone().to_command_and_envs();
+        assert_eq!(
+            step.when,
+            Some(Condition::All(vec![
+                Condition::Env(s!("CI=true")),
+                Condition::Not(Box::new(Condition::FileExists(s!("Cargo.lock")))),
+                Condition::Any(vec![Condition::CommandSucceeds(s!("which docker"))]),
+            ]))
+        );
+    }
+
+    #[test]
+    fn test_params() {
+        let foo_commands = "exec:
+    foo:
+        params:
+            - name: env
+              type: enum
+              values: [staging, production]
+              default: staging
+              help: Where to deploy
+            - name: replicas
+              type: int
+              default: 2
+            - name: tag
+              required: true
+        commands: 
+            - bar";
+        let result = serde_yaml::from_str::<ProjectConfig>(foo_commands).unwrap();
+        let foo = result.exec.get("foo").unwrap();
+        assert_eq!(
+            foo.params,
+            vec![
+                ParamConfig {
+                    name: s!("env"),
+                    kind: ParamType::Enum,
+                    values: vec![s!("staging"), s!("production")],
+                    default: Some(ParamValue::Text(s!("staging"))),
+                    required: false,
+                    help: Some(s!("Where to deploy")),
+                },
+                ParamConfig {
+                    name: s!("replicas"),
+                    kind: ParamType::Int,
+                    values: Vec::new(),
+                    default: Some(ParamValue::Int(2)),
+                    required: false,
+                    help: None,
+                },
+                ParamConfig {
+                    name: s!("tag"),
+                    kind: ParamType::String,
+                    values: Vec::new(),
+                    default: None,
+                    required: true,
+                    help: None,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_matrix_combinations() {
+        let foo_commands = "exec:
+    foo:
+        matrix:
+            target: [linux, mac]
+            toolchain: [stable, nightly]
+            exclude:
+                - target: mac
+                  toolchain: nightly
+            include:
+                - target: windows
+                  toolchain: stable
+        commands: 
+            - bar";
+        let result = serde_yaml::from_str::<ProjectConfig>(foo_commands).unwrap();
+        let foo = result.exec.get("foo").unwrap();
+        let combinations: Vec<Vec<(String, String)>> = foo
+            .matrix
+            .as_ref()
+            .unwrap()
+            .combinations()
+            .unwrap()
+            .into_iter()
+            .map(|x| x.into_iter().collect())
+            .collect();
+
+        let combination = |target: &str, toolchain: &str| {
+            vec![(s!("target"), s!(target)), (s!("toolchain"), s!(toolchain))]
+        };
+        assert_eq!(
+            combinations,
+            vec![
+                combination("linux", "stable"),
+                combination("linux", "nightly"),
+                combination("mac", "stable"),
+                combination("windows", "stable"),
+            ]
+        );
+    }
 }
@@ -1,8 +1,17 @@
+use libs::secrets::Secrets;
+use std::cmp::min;
 use std::collections::HashMap;
 use std::env::{self, current_exe, var};
 use std::io::Error as IoError;
+use std::fs::{self, OpenOptions};
+use std::io::{BufRead, BufReader, Read, Write};
 use std::path::PathBuf;
-use std::process::{Child, Command, Output, Stdio};
+use std::process::{self, Child, Command, ExitStatus, Output, Stdio};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
 
 pub struct CliError {
     pub code: i32,
@@ -20,6 +29,11 @@ impl CliError {
 
 pub type CliResult = Result<i32, CliError>;
 
+/**
+ * The code for a command that ran longer than it's `timeout`, the same as coreutils' `timeout`.
+ */
+pub const TIMEOUT_EXIT_CODE: i32 = 124;
+
 pub type CliParseResults = Result<(), CliParseError>;
 
 pub struct CliParseError {
@@ -33,27 +47,674 @@ impl From<CliParseError> for CliError {
     }
 }
 
+/**
+ * Keeps track of the processes that are running, so they can be stopped when a sibling fails,
+ * or when the run is cancelled. Processes that don't share the terminal are started in their
+ * own process group, which is what gets stopped. For the others it's the process, and
+ * everything it started.
+ */
+#[derive(Debug, Clone)]
+pub struct RunningProcesses {
+    pids: Arc<Mutex<HashMap<u32, bool>>>,
+    stopping: Arc<AtomicBool>,
+    cancelled: Arc<AtomicBool>,
+}
+
+impl RunningProcesses {
+    pub fn new() -> Self {
+        return RunningProcesses {
+            pids: Arc::new(Mutex::new(HashMap::new())),
+            stopping: Arc::new(AtomicBool::new(false)),
+            cancelled: Arc::new(AtomicBool::new(false)),
+        };
+    }
+
+    fn add(&self, pid: u32, own_process_group: bool) {
+        self.pids.lock().unwrap().insert(pid, own_process_group);
+    }
+
+    fn remove(&self, pid: u32) {
+        self.pids.lock().unwrap().remove(&pid);
+    }
+
+    pub fn terminate_all(&self) {
+        self.stopping.store(true, Ordering::SeqCst);
+        for (pid, own_process_group) in self.pids.lock().unwrap().iter() {
+            debug!("Terminating process {}", pid);
+            signal_process(*pid, *own_process_group, Signal::Terminate);
+        }
+    }
+
+    /**
+     * Forwards a SIGINT, like a control-c while waiting on a process, to everything running.
+     * Use `kill_all` for processes that don't stop.
+     */
+    pub fn interrupt_all(&self) {
+        self.stopping.store(true, Ordering::SeqCst);
+        self.cancelled.store(true, Ordering::SeqCst);
+        for (pid, own_process_group) in self.pids.lock().unwrap().iter() {
+            debug!("Interrupting process {}", pid);
+            signal_process(*pid, *own_process_group, Signal::Interrupt);
+        }
+    }
+
+    /**
+     * What a control-c does while waiting for a process, besides forwarding it. Only flags are
+     * set, because it's called from the signal handler.
+     */
+    fn mark_cancelled(&self) {
+        self.stopping.store(true, Ordering::SeqCst);
+        self.cancelled.store(true, Ordering::SeqCst);
+    }
+
+    pub fn kill_all(&self) {
+        for (pid, own_process_group) in self.pids.lock().unwrap().iter() {
+            warn!("Killing process {}!", pid);
+            signal_process(*pid, *own_process_group, Signal::Kill);
+        }
+    }
+
+    /**
+     * True once the processes have been told to stop, so nothing new should be started.
+     */
+    pub fn is_stopping(&self) -> bool {
+        return self.stopping.load(Ordering::SeqCst);
+    }
+
+    /**
+     * Sleeps for `duration`, or until the processes are told to stop. True when it slept the
+     * whole time.
+     */
+    pub fn sleep(&self, duration: Duration) -> bool {
+        let deadline = Instant::now() + duration;
+        loop {
+            if self.is_stopping() {
+                return false;
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                return true;
+            }
+            thread::sleep(min(deadline - now, Duration::from_millis(100)));
+        }
+    }
+
+    /**
+     * Marks the processes as cancelled on every control-c, until the handler is dropped.
+     */
+    pub fn cancel_on_interrupt(&self) -> InterruptHandler {
+        let processes = self.clone();
+        return on_interrupt(move || processes.mark_cancelled());
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        return self.cancelled.load(Ordering::SeqCst);
+    }
+}
+
+/**
+ * Runs an action on every control-c, until it's dropped. `wait_for_output` only watches for a
+ * control-c while it waits, and once it stops, a control-c that nothing else watches for is
+ * lost, so one of these should be kept while there is something to cancel.
+ */
+pub struct InterruptHandler {
+    #[cfg(unix)]
+    handler: Option<signal_hook::SigId>,
+}
+
+/**
+ * `action` is run from the signal handler, so it should only set flags.
+ */
+#[cfg(unix)]
+pub fn on_interrupt<F>(action: F) -> InterruptHandler
+where
+    F: Fn() + Sync + Send + 'static,
+{
+    let handler = unsafe { signal_hook::register(signal_hook::SIGINT, action) };
+
+    return InterruptHandler {
+        handler: match handler {
+            Ok(handler) => Some(handler),
+            Err(err) => {
+                warn!("Unable to watch for control-c: {}", err);
+                None
+            }
+        },
+    };
+}
+
+#[cfg(windows)]
+pub fn on_interrupt<F>(_action: F) -> InterruptHandler
+where
+    F: Fn() + Sync + Send + 'static,
+{
+    return InterruptHandler {};
+}
+
+#[cfg(unix)]
+impl Drop for InterruptHandler {
+    fn drop(&mut self) {
+        if let Some(handler) = self.handler.take() {
+            signal_hook::unregister(handler);
+        }
+    }
+}
+
+enum Signal {
+    Interrupt,
+    Terminate,
+    Kill,
+}
+
+#[cfg(unix)]
+fn signal_process(pid: u32, own_process_group: bool, signal: Signal) {
+    use libc::{kill, SIGINT, SIGKILL, SIGTERM};
+    let signal = match signal {
+        Signal::Interrupt => SIGINT,
+        Signal::Terminate => SIGTERM,
+        Signal::Kill => SIGKILL,
+    };
+    if own_process_group {
+        unsafe {
+            kill(-(pid as i32), signal);
+        }
+        return;
+    }
+
+    // Without it's own group, everything the process started has to be found one by one
+    for pid in with_descendants(pid) {
+        unsafe {
+            kill(pid as i32, signal);
+        }
+    }
+}
+
+/**
+ * The process, and everything it started, according to `ps`. Only the process itself when
+ * `ps` can't be run.
+ */
+#[cfg(unix)]
+fn with_descendants(pid: u32) -> Vec<u32> {
+    let processes: Vec<(u32, u32)> = match Command::new("ps")
+        .args(&["-A", "-o", "pid=", "-o", "ppid="])
+        .output()
+    {
+        Ok(output) => String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .filter_map(|line| {
+                let mut words = line.split_whitespace().map(|x| x.parse::<u32>().ok());
+                match (words.next(), words.next()) {
+                    (Some(Some(pid)), Some(Some(parent))) => Some((pid, parent)),
+                    _ => None,
+                }
+            }).collect(),
+        Err(err) => {
+            debug!("Unable to find the children of {}: {}", pid, err);
+            Vec::new()
+        }
+    };
+
+    let mut result = vec![pid];
+    let mut i = 0;
+    while i < result.len() {
+        let parent = result[i];
+        result.extend(
+            processes
+                .iter()
+                .filter(|x| x.1 == parent)
+                .map(|x| x.0),
+        );
+        i += 1;
+    }
+
+    return result;
+}
+
+#[cfg(windows)]
+fn signal_process(pid: u32, _own_process_group: bool, _signal: Signal) {
+    let _ = Command::new("taskkill")
+        .arg("/F")
+        .arg("/T")
+        .arg("/PID")
+        .arg(format!("{}", pid))
+        .output();
+}
+
+enum OutputMode {
+    Inherit,
+    Capture,
+    Prefix(String),
+    /**
+     * Shows the output, like `Prefix` when there is a prefix, while keeping a copy of it when
+     * `record` is set, and writing it to `log_file`. Also used to mask secrets, which can't be
+     * done when the output goes straight to the terminal.
+     */
+    Tee {
+        prefix: Option<String>,
+        record: bool,
+        log_file: Option<PathBuf>,
+    },
+}
+
+/**
+ * How a step gets started. `Shell` hands the string, untouched, to the shell. `Direct` starts
+ * the executable with exactly the given arguments, without a shell in between. `Script` writes
+ * the contents to a temporary file, which is given to the interpreter.
+ */
+#[derive(Debug, Clone, PartialEq)]
+pub enum CommandLine {
+    Shell(String),
+    Direct(PathBuf, Vec<String>),
+    Script {
+        interpreter: PathBuf,
+        interpreter_args: Vec<String>,
+        contents: String,
+        script_args: Vec<String>,
+    },
+}
+
+impl CommandLine {
+    /**
+     * The program, and it's arguments, that will be started. A script file is only written right
+     * before it runs, so it shows up as `<script>`.
+     */
+    pub fn to_args(&self) -> Vec<String> {
+        return match self {
+            CommandLine::Shell(command) => {
+                let (shell, flag) = default_shell();
+                vec![s!(shell), s!(flag), command.clone()]
+            }
+            CommandLine::Direct(cmd, args) => {
+                let mut result = vec![s!(cmd.to_string_lossy())];
+                result.extend(args.iter().cloned());
+                result
+            }
+            CommandLine::Script {
+                interpreter,
+                interpreter_args,
+                script_args,
+                ..
+            } => {
+                let mut result = vec![s!(interpreter.to_string_lossy())];
+                result.extend(interpreter_args.iter().cloned());
+                result.push(s!("<script>"));
+                result.extend(script_args.iter().cloned());
+                result
+            }
+        };
+    }
+}
+
+static SCRIPT_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
 pub fn execute_external_command(
     cmd: &PathBuf,
     args: &[String],
     extra_env: HashMap<String, String>,
 ) -> CliResult {
     let command_exe = format!("{:?}{}", cmd.to_str().unwrap(), env::consts::EXE_SUFFIX);
+    let command = shell_command(join_command(command_exe, args));
 
-    return match run_command(command_exe, args, extra_env, false) {
+    return match run_command(
+        command,
+        extra_env,
+        OutputMode::Inherit,
+        None,
+        None,
+        &Secrets::default(),
+    ) {
         (_, _, Ok(code)) => Ok(code),
         (_, _, Err(err)) => Err(err),
     };
 }
 
+/**
+ * Settings for a single run of a command line.
+ */
+#[derive(Debug, Clone, Default)]
+pub struct RunOptions {
+    /**
+     * Where the command runs, instead of the current directory.
+     */
+    pub working_dir: Option<PathBuf>,
+    /**
+     * When set, every line of output is written with `[prefix]` in front of it, which keeps
+     * output readable when multiple commands run at the same time.
+     */
+    pub prefix: Option<String>,
+    /**
+     * Throw away the output, for commands that only matter for their exit code.
+     */
+    pub quiet: bool,
+    /**
+     * Stops the command once it has run this long, and fails with `TIMEOUT_EXIT_CODE`.
+     */
+    pub timeout: Option<Duration>,
+    /**
+     * Keeps a copy of the output, while still showing it. Output from `stdout` and `stderr` is
+     * kept together, in the order it was read.
+     */
+    pub record_output: bool,
+    /**
+     * Also writes the output to this file, as it's read. The file is appended to, so retries
+     * end up in the same file.
+     */
+    pub log_file: Option<PathBuf>,
+    /**
+     * Masked in the output, wherever it goes, and in the debug logs. The output doesn't go
+     * straight to the terminal when one of them is in the command line, or it's environment.
+     */
+    pub secrets: Secrets,
+}
+
+pub fn execute_command_line(
+    command_line: &CommandLine,
+    extra_env: HashMap<String, String>,
+    options: &RunOptions,
+    processes: &RunningProcesses,
+) -> CliResult {
+    return execute_command_line_with_output(command_line, extra_env, options, processes).0;
+}
+
+/**
+ * The same as `execute_command_line`, also returning the output when `record_output` is set.
+ */
+pub fn execute_command_line_with_output(
+    command_line: &CommandLine,
+    extra_env: HashMap<String, String>,
+    options: &RunOptions,
+    processes: &RunningProcesses,
+) -> (CliResult, Option<String>) {
+    let (command, script_file) = match build_command(command_line, options) {
+        Ok(value) => value,
+        Err(err) => return (Err(err), None),
+    };
+
+    let has_secrets = has_secrets(command_line, &extra_env, &options.secrets);
+    let output_mode = match options.prefix {
+        _ if options.quiet => OutputMode::Capture,
+        ref prefix if options.record_output || options.log_file.is_some() || has_secrets => {
+            OutputMode::Tee {
+                prefix: prefix.clone(),
+                record: options.record_output,
+                log_file: options.log_file.clone(),
+            }
+        }
+        Some(ref prefix) => OutputMode::Prefix(prefix.clone()),
+        None => OutputMode::Inherit,
+    };
+    let result = run_command(
+        command,
+        extra_env,
+        output_mode,
+        Some(processes),
+        options.timeout,
+        &options.secrets,
+    );
+
+    if let Some(path) = script_file {
+        if let Err(err) = fs::remove_file(&path) {
+            debug!("Unable to remove {:?}: {}", path, err);
+        }
+    }
+
+    let output = if options.record_output && !options.quiet {
+        Some(result.0)
+    } else {
+        None
+    };
+
+    return (result.2, output);
+}
+
+/**
+ * True when the process could show one of the secrets, because it's in the command line, or the
+ * environment it gets.
+ */
+fn has_secrets(
+    command_line: &CommandLine,
+    extra_env: &HashMap<String, String>,
+    secrets: &Secrets,
+) -> bool {
+    if secrets.is_empty() {
+        return false;
+    }
+
+    let mut texts: Vec<String> = env::vars().map(|(_, value)| value).collect();
+    texts.extend(extra_env.values().cloned());
+    match command_line {
+        CommandLine::Shell(command) => texts.push(command.clone()),
+        CommandLine::Direct(cmd, args) => {
+            texts.push(s!(cmd.to_string_lossy()));
+            texts.extend(args.iter().cloned());
+        }
+        CommandLine::Script {
+            contents,
+            script_args,
+            ..
+        } => {
+            texts.push(contents.clone());
+            texts.extend(script_args.iter().cloned());
+        }
+    }
+
+    return secrets.appear_in(texts.iter().map(|x| x.as_str()));
+}
+
+/**
+ * The process to start for `command_line`, and the script file that has to be removed once
+ * it's done, if there is one.
+ */
+fn build_command(
+    command_line: &CommandLine,
+    options: &RunOptions,
+) -> Result<(Command, Option<PathBuf>), CliError> {
+    if let Some(ref working_dir) = options.working_dir {
+        if !working_dir.is_dir() {
+            return Err(CliError::new(
+                10,
+                format!("The working directory {:?} doesn't exist", working_dir),
+            ));
+        }
+    }
+
+    let mut script_file: Option<PathBuf> = None;
+    let mut command = match command_line {
+        CommandLine::Shell(command) => shell_command(command.clone()),
+        CommandLine::Direct(cmd, args) => {
+            let mut command = Command::new(cmd);
+            command.args(args);
+            command
+        }
+        CommandLine::Script {
+            interpreter,
+            interpreter_args,
+            contents,
+            script_args,
+        } => {
+            let path = match write_script(interpreter, contents) {
+                Ok(path) => path,
+                Err(err) => {
+                    return Err(CliError::new(
+                        10,
+                        format!("Unable to write script to a temporary file: {}", err),
+                    ));
+                }
+            };
+
+            let mut command = Command::new(interpreter);
+            command.args(interpreter_args).arg(&path).args(script_args);
+            script_file = Some(path);
+            command
+        }
+    };
+
+    if let Some(ref working_dir) = options.working_dir {
+        command.current_dir(working_dir);
+    }
+
+    return Ok((command, script_file));
+}
+
+/**
+ * A process that keeps running in the background, like a service that a command's steps
+ * need, until it's stopped. It has it's own process group, so everything it started is
+ * stopped with it.
+ */
+pub struct BackgroundProcess {
+    child: Child,
+    readers: Vec<JoinHandle<()>>,
+    script_file: Option<PathBuf>,
+}
+
+/**
+ * Starts `command_line` without waiting for it, in it's own process group and with no input.
+ * The output is shown like a step's, and every line of it is also sent to `lines`.
+ */
+pub fn start_background_process(
+    command_line: &CommandLine,
+    extra_env: HashMap<String, String>,
+    options: &RunOptions,
+    lines: Option<Sender<String>>,
+) -> Result<BackgroundProcess, CliError> {
+    let (command, script_file) = build_command(command_line, options)?;
+
+    let log_file = match options.log_file {
+        Some(ref path) => match OpenOptions::new().create(true).append(true).open(path) {
+            Ok(file) => Some(Arc::new(Mutex::new(file))),
+            Err(err) => {
+                return Err(CliError::new(
+                    10,
+                    format!("Unable to write the log to {:?}: {}", path, err),
+                ))
+            }
+        },
+        None => None,
+    };
+
+    let env_map = build_env_updates(extra_env, &options.secrets);
+    let mut child = match spawn_cmd(command, env_map, Stdio::null(), Stdio::piped(), Stdio::piped(), true) {
+        Ok(child) => child,
+        Err(err) => {
+            return Err(CliError::new(
+                10,
+                format!("Unable to execute command: {}", err),
+            ))
+        }
+    };
+
+    let mut readers: Vec<JoinHandle<()>> = Vec::new();
+    if let Some(stdout) = child.stdout.take() {
+        readers.push(forward_lines(
+            stdout,
+            options.prefix.clone(),
+            false,
+            None,
+            log_file.clone(),
+            options.secrets.clone(),
+            lines.clone(),
+        ));
+    }
+    if let Some(stderr) = child.stderr.take() {
+        readers.push(forward_lines(
+            stderr,
+            options.prefix.clone(),
+            true,
+            None,
+            log_file,
+            options.secrets.clone(),
+            lines,
+        ));
+    }
+
+    return Ok(BackgroundProcess {
+        child: child,
+        readers: readers,
+        script_file: script_file,
+    });
+}
+
+impl BackgroundProcess {
+    /**
+     * The exit code, once the process has exited on it's own.
+     */
+    pub fn exit_code(&mut self) -> Option<i32> {
+        return match self.child.try_wait() {
+            Ok(Some(status)) => Some(status.code().unwrap_or(0)),
+            _ => None,
+        };
+    }
+
+    /**
+     * Asks the process to stop with a SIGTERM, and kills it if it's still running after
+     * `grace`. Returns the exit code, when it has one.
+     */
+    pub fn stop(mut self, grace: Duration) -> Option<i32> {
+        let pid = self.child.id();
+        let mut exit_code = self.exit_code();
+        if exit_code.is_none() {
+            signal_process(pid, true, Signal::Terminate);
+            let deadline = Instant::now() + grace;
+            while exit_code.is_none() && Instant::now() < deadline {
+                thread::sleep(Duration::from_millis(50));
+                exit_code = self.exit_code();
+            }
+        }
+        if exit_code.is_none() {
+            warn!("Killing process {}!", pid);
+            signal_process(pid, true, Signal::Kill);
+            exit_code = self.child.wait().ok().and_then(|x| x.code());
+        }
+
+        for reader in self.readers.drain(..) {
+            let _ = reader.join();
+        }
+        if let Some(ref path) = self.script_file {
+            if let Err(err) = fs::remove_file(path) {
+                debug!("Unable to remove {:?}: {}", path, err);
+            }
+        }
+
+        return exit_code;
+    }
+}
+
+fn write_script(interpreter: &PathBuf, contents: &String) -> Result<PathBuf, IoError> {
+    let extension = match interpreter.file_stem().and_then(|x| x.to_str()) {
+        Some("cmd") => ".cmd",
+        Some("powershell") | Some("pwsh") => ".ps1",
+        _ => "",
+    };
+    let file_name = format!(
+        "inc-script-{}-{}{}",
+        process::id(),
+        SCRIPT_COUNTER.fetch_add(1, Ordering::SeqCst),
+        extension
+    );
+    let path = env::temp_dir().join(file_name);
+
+    let mut file = OpenOptions::new().write(true).create_new(true).open(&path)?;
+    file.write_all(contents.as_bytes())?;
+
+    return Ok(path);
+}
+
 pub fn execute_external_command_for_output(
     cmd: &PathBuf,
     args: &[String],
     extra_env: HashMap<String, String>,
 ) -> Result<String, CliError> {
     let command_exe = format!("{}{}", cmd.to_str().unwrap(), env::consts::EXE_SUFFIX);
+    let command = shell_command(join_command(command_exe, args));
 
-    return match run_command(command_exe, args, extra_env, true) {
+    return match run_command(
+        command,
+        extra_env,
+        OutputMode::Capture,
+        None,
+        None,
+        &Secrets::default(),
+    ) {
         (stdout, _, Ok(_)) => Ok(stdout.trim().to_string()),
         (stdout, stderr, Err(err)) => {
             for line in stdout.lines() {
@@ -67,12 +728,7 @@ pub fn execute_external_command_for_output(
     };
 }
 
-fn run_command(
-    cmd: String,
-    args: &[String],
-    extra_env: HashMap<String, String>,
-    capture_output: bool,
-) -> (String, String, Result<i32, CliError>) {
+fn join_command(cmd: String, args: &[String]) -> String {
     let mut command_string = String::new();
     command_string.push_str(cmd.as_str());
     for arg in args.iter() {
@@ -80,14 +736,59 @@ fn run_command(
         command_string.push_str(arg.as_str());
     }
 
-    let (stdout, stderr) = if capture_output {
-        (Stdio::piped(), Stdio::piped())
-    } else {
-        (Stdio::inherit(), Stdio::inherit())
+    return command_string;
+}
+
+fn run_command(
+    command: Command,
+    extra_env: HashMap<String, String>,
+    output_mode: OutputMode,
+    processes: Option<&RunningProcesses>,
+    timeout: Option<Duration>,
+    secrets: &Secrets,
+) -> (String, String, Result<i32, CliError>) {
+    let description = secrets.mask(&format!("{:?}", command));
+    let (stdout, stderr) = match output_mode {
+        OutputMode::Inherit => (Stdio::inherit(), Stdio::inherit()),
+        _ => (Stdio::piped(), Stdio::piped()),
+    };
+
+    let log_file = match output_mode {
+        OutputMode::Tee {
+            log_file: Some(ref path),
+            ..
+        } => match OpenOptions::new().create(true).append(true).open(path) {
+            Ok(file) => Some(Arc::new(Mutex::new(file))),
+            Err(err) => {
+                return (
+                    s!(""),
+                    s!(""),
+                    Err(CliError::new(
+                        10,
+                        format!("Unable to write the log to {:?}: {}", path, err),
+                    )),
+                )
+            }
+        },
+        _ => None,
     };
 
-    let env_map = build_env_updates(extra_env);
-    let child = match spawn_cmd(command_string, env_map, stdout, stderr) {
+    let env_map = build_env_updates(extra_env, secrets);
+    // A process sharing the terminal stays in our process group, so it can read from it and
+    // gets control-c from it. That includes a process with it's output kept or masked, when it
+    // is the only one running.
+    let own_process_group = match output_mode {
+        OutputMode::Inherit | OutputMode::Tee { prefix: None, .. } => false,
+        _ => processes.is_some(),
+    };
+    // Reading the terminal from another process group would stop the process, and the step
+    // would hang
+    let stdin = if own_process_group {
+        Stdio::null()
+    } else {
+        Stdio::inherit()
+    };
+    let mut child = match spawn_cmd(command, env_map, stdin, stdout, stderr, own_process_group) {
         Err(value) => {
             return (
                 s!(""),
@@ -101,12 +802,92 @@ fn run_command(
         Ok(child) => child,
     };
 
-    let result = wait_for_output(child);
+    let child_id = child.id();
+    if let Some(processes) = processes {
+        processes.add(child_id, own_process_group);
+    }
+
+    let mut readers: Vec<JoinHandle<()>> = Vec::new();
+    let recorded = Arc::new(Mutex::new(String::new()));
+    let (prefix, record) = match output_mode {
+        OutputMode::Prefix(prefix) => (Some(Some(prefix)), None),
+        OutputMode::Tee { prefix, record, .. } => {
+            (Some(prefix), if record { Some(recorded.clone()) } else { None })
+        }
+        _ => (None, None),
+    };
+    if let Some(prefix) = prefix {
+        if let Some(stdout) = child.stdout.take() {
+            readers.push(forward_lines(
+                stdout,
+                prefix.clone(),
+                false,
+                record.clone(),
+                log_file.clone(),
+                secrets.clone(),
+                None,
+            ));
+        }
+        if let Some(stderr) = child.stderr.take() {
+            readers.push(forward_lines(
+                stderr,
+                prefix,
+                true,
+                record.clone(),
+                log_file,
+                secrets.clone(),
+                None,
+            ));
+        }
+    }
+
+    let interrupted = Arc::new(AtomicBool::new(false));
+    let (finished, receiver) = channel::<()>();
+    let watchdog = {
+        let interrupted = interrupted.clone();
+        thread::spawn(move || {
+            watch_process(child_id, own_process_group, timeout, &interrupted, &receiver)
+        })
+    };
+
+    let result = wait_for_output(child, interrupted, processes.cloned());
+
+    drop(finished);
+    let timed_out = watchdog.join().unwrap_or(false);
+    // A control-c can reach a process sharing the terminal, and end it, before our handler runs
+    if let (Ok(ref output), Some(processes)) = (&result, processes) {
+        if !timed_out && was_interrupted(&output.status) {
+            processes.mark_cancelled();
+        }
+    }
+    for reader in readers {
+        let _ = reader.join();
+    }
+    if let Some(processes) = processes {
+        processes.remove(child_id);
+    }
+    let recorded = recorded.lock().unwrap().clone();
+
+    if timed_out {
+        return (
+            recorded,
+            s!(""),
+            Err(CliError::new(
+                TIMEOUT_EXIT_CODE,
+                format!("Timed out after {:?}", timeout.unwrap()),
+            )),
+        );
+    }
 
     return match result {
+        Ok(ref output) if record.is_some() => (
+            recorded,
+            s!(""),
+            Ok(output.status.code().unwrap_or_else(|| 0)),
+        ),
         Ok(output) => (
-            String::from_utf8_lossy(&output.stdout).to_string(),
-            String::from_utf8_lossy(&output.stderr).to_string(),
+            secrets.mask(&String::from_utf8_lossy(&output.stdout)),
+            secrets.mask(&String::from_utf8_lossy(&output.stderr)),
             Ok(output.status.code().unwrap_or_else(|| 0)),
         ),
         Err(value) => (
@@ -114,42 +895,199 @@ fn run_command(
             s!(""),
             Err(CliError {
                 code: 10,
-                message: format!("Unable to run {:?} it returned {}", args, value),
+                message: format!("Unable to run {} it returned {}", description, value),
             }),
         ),
     };
 }
 
+/**
+ * Waits for `finished`, stopping the process with `stop_gracefully` once it has run longer than
+ * `timeout`, or after a control-c. True when it timed out.
+ */
+fn watch_process(
+    pid: u32,
+    own_process_group: bool,
+    timeout: Option<Duration>,
+    interrupted: &AtomicBool,
+    finished: &Receiver<()>,
+) -> bool {
+    let started = Instant::now();
+    loop {
+        match finished.recv_timeout(Duration::from_millis(100)) {
+            Err(RecvTimeoutError::Timeout) => {}
+            _ => return false,
+        }
+
+        if interrupted.load(Ordering::SeqCst) {
+            stop_gracefully(pid, own_process_group, finished);
+            return false;
+        }
+        if let Some(timeout) = timeout {
+            if started.elapsed() >= timeout {
+                debug!("Timed out after {:?}, stopping the process", timeout);
+                stop_gracefully(pid, own_process_group, finished);
+                return true;
+            }
+        }
+    }
+}
+
+/**
+ * How a process is stopped, after a control-c or a timeout: a SIGINT, to the whole process group
+ * when it has it's own, because the terminal only sends it to our group. Then a SIGKILL if it's
+ * still around five seconds later.
+ */
+fn stop_gracefully(pid: u32, own_process_group: bool, finished: &Receiver<()>) {
+    signal_process(pid, own_process_group, Signal::Interrupt);
+    for i in (0..50).rev() {
+        if let Err(RecvTimeoutError::Timeout) = finished.recv_timeout(Duration::from_millis(100)) {
+            if i % 10 == 0 && i >= 10 {
+                info!("Waiting {} more seconds for process to go away", i / 10);
+            }
+            continue;
+        }
+        return;
+    }
+    warn!("Killing process!");
+    signal_process(pid, own_process_group, Signal::Kill);
+}
+
+/**
+ * Writes every line from the process to our own output, with `[prefix]` in front of it when
+ * there is one, keeps a copy of it in `record`, writes it to `log_file` and sends it to `lines`.
+ * Every line has the `secrets` masked first.
+ */
+fn forward_lines<R: Read + Send + 'static>(
+    source: R,
+    prefix: Option<String>,
+    is_stderr: bool,
+    record: Option<Arc<Mutex<String>>>,
+    log_file: Option<Arc<Mutex<fs::File>>>,
+    secrets: Secrets,
+    lines: Option<Sender<String>>,
+) -> JoinHandle<()> {
+    let prefix = match prefix {
+        Some(prefix) => format!("[{}] ", prefix),
+        None => s!(""),
+    };
+    if prefix.is_empty() && secrets.is_empty() && lines.is_none() {
+        return thread::spawn(move || forward_output(source, is_stderr, record, log_file));
+    }
+
+    return thread::spawn(move || {
+        for line in BufReader::new(source).lines() {
+            match line {
+                Ok(line) => {
+                    let line = secrets.mask(&line);
+                    if is_stderr {
+                        eprintln!("{}{}", prefix, line);
+                    } else {
+                        println!("{}{}", prefix, line);
+                    }
+                    if let Some(ref record) = record {
+                        let mut record = record.lock().unwrap();
+                        record.push_str(&line);
+                        record.push('\n');
+                    }
+                    if let Some(ref log_file) = log_file {
+                        let _ = writeln!(log_file.lock().unwrap(), "{}", line);
+                    }
+                    if let Some(ref lines) = lines {
+                        let _ = lines.send(line);
+                    }
+                }
+                Err(_) => return,
+            }
+        }
+    });
+}
+
+#[cfg(unix)]
+fn was_interrupted(status: &ExitStatus) -> bool {
+    use std::os::unix::process::ExitStatusExt;
+    return status.signal() == Some(libc::SIGINT);
+}
+
 #[cfg(windows)]
-fn wait_for_output(child: Child) -> Result<Output, std::io::Error> {
+fn was_interrupted(_status: &ExitStatus) -> bool {
+    return false;
+}
+
+/**
+ * Like `forward_lines`, without waiting for the end of a line, so a prompt shows up before
+ * there is an answer to it.
+ */
+fn forward_output<R: Read>(
+    mut source: R,
+    is_stderr: bool,
+    record: Option<Arc<Mutex<String>>>,
+    log_file: Option<Arc<Mutex<fs::File>>>,
+) {
+    let mut buffer = [0; 4096];
+    let mut partial_line: Vec<u8> = Vec::new();
+    loop {
+        let read = match source.read(&mut buffer) {
+            Ok(0) | Err(_) => break,
+            Ok(read) => read,
+        };
+        let output = &buffer[..read];
+        if is_stderr {
+            let mut stderr = std::io::stderr();
+            let _ = stderr.write_all(output).and_then(|_| stderr.flush());
+        } else {
+            let mut stdout = std::io::stdout();
+            let _ = stdout.write_all(output).and_then(|_| stdout.flush());
+        }
+        if let Some(ref log_file) = log_file {
+            let _ = log_file.lock().unwrap().write_all(output);
+        }
+
+        // Only whole lines are recorded, so a character split between reads stays intact
+        if let Some(ref record) = record {
+            partial_line.extend_from_slice(output);
+            if let Some(end) = partial_line.iter().rposition(|x| *x == b'\n') {
+                let lines: Vec<u8> = partial_line.drain(..end + 1).collect();
+                record.lock().unwrap().push_str(&String::from_utf8_lossy(&lines));
+            }
+        }
+    }
+
+    if let Some(ref record) = record {
+        if !partial_line.is_empty() {
+            let mut record = record.lock().unwrap();
+            record.push_str(&String::from_utf8_lossy(&partial_line));
+            record.push('\n');
+        }
+    }
+}
+
+#[cfg(windows)]
+fn wait_for_output(
+    child: Child,
+    _interrupted: Arc<AtomicBool>,
+    _processes: Option<RunningProcesses>,
+) -> Result<Output, std::io::Error> {
     return child.wait_with_output();
 }
 
+/**
+ * Waits for the process to exit. A control-c sets `interrupted`, so `watch_process` stops the
+ * process, and marks `processes` as cancelled, so nothing else is started, other than `finally`
+ * steps. Only flags are set, because it's called from the signal handler.
+ */
 #[cfg(unix)]
-fn wait_for_output(child: Child) -> Result<Output, std::io::Error> {
-    use libc::{kill, SIGKILL, SIGINT};
-    use std::thread::sleep;
-    use std::time::Duration;
-    let child_id = child.id();
-
+fn wait_for_output(
+    child: Child,
+    interrupted: Arc<AtomicBool>,
+    processes: Option<RunningProcesses>,
+) -> Result<Output, std::io::Error> {
     let signal = unsafe {
         match signal_hook::register(signal_hook::SIGINT, move || {
-            kill(child_id as i32, SIGINT);
-            sleep(Duration::from_millis(100));
-            for i in (0..50).rev() {
-                let is_dead: i32 = kill(child_id as i32, 0 as i32);
-                if is_dead == 0 {
-                    return;
-                }
-                if i % 10 == 0 && i >= 10 {
-                    info!("Waiting {} more seconds for process to go away", i / 10);
-                }
-
-                sleep(Duration::from_millis(100));
+            if let Some(ref processes) = processes {
+                processes.mark_cancelled();
             }
-            warn!("Killing process!");
-            kill(child_id as i32, SIGKILL);
-            std::process::exit(1);
+            interrupted.store(true, Ordering::SeqCst);
         }) {
             Ok(sig) => sig,
             Err(_) => {
@@ -165,39 +1103,55 @@ fn wait_for_output(child: Child) -> Result<Output, std::io::Error> {
     return result;
 }
 
-#[cfg(windows)]
-fn spawn_cmd<'a>(
-    command: String,
+fn spawn_cmd(
+    mut command: Command,
     env: HashMap<String, String>,
+    stdin: Stdio,
     stdout: Stdio,
     stderr: Stdio,
+    own_process_group: bool,
 ) -> Result<Child, IoError> {
-    return Command::new("cmd")
-        .arg("/C")
-        .stdout(stdout)
-        .stderr(stderr)
-        .arg(command)
-        .envs(&env)
-        .spawn();
+    command.stdin(stdin).stdout(stdout).stderr(stderr).envs(&env);
+    if own_process_group {
+        use_own_process_group(&mut command);
+    }
+
+    return command.spawn();
+}
+
+#[cfg(windows)]
+fn default_shell() -> (&'static str, &'static str) {
+    return ("cmd", "/C");
 }
 
 #[cfg(unix)]
-fn spawn_cmd(
-    command: String,
-    env: HashMap<String, String>,
-    stdout: Stdio,
-    stderr: Stdio,
-) -> Result<Child, IoError> {
-    return Command::new("sh")
-        .arg("-c")
-        .stdout(stdout)
-        .stderr(stderr)
-        .arg(command)
-        .envs(&env)
-        .spawn();
+fn default_shell() -> (&'static str, &'static str) {
+    return ("sh", "-c");
 }
 
-fn build_env_updates(extra_env: HashMap<String, String>) -> HashMap<String, String> {
+fn shell_command(command: String) -> Command {
+    let (shell, flag) = default_shell();
+    let mut shell = Command::new(shell);
+    shell.arg(flag).arg(command);
+    return shell;
+}
+
+#[cfg(windows)]
+fn use_own_process_group(_command: &mut Command) {}
+
+/**
+ * Lets the whole process tree be stopped at once, instead of leaving grandchildren behind.
+ */
+#[cfg(unix)]
+fn use_own_process_group(command: &mut Command) {
+    use std::os::unix::process::CommandExt;
+    command.process_group(0);
+}
+
+fn build_env_updates(
+    extra_env: HashMap<String, String>,
+    secrets: &Secrets,
+) -> HashMap<String, String> {
     let mut results: HashMap<String, String> = HashMap::new();
     results.insert(String::from("PATH"), build_path());
 
@@ -209,7 +1163,7 @@ fn build_env_updates(extra_env: HashMap<String, String>) -> HashMap<String, Stri
         results.insert(key, value);
     }
 
-    debug!("Using ENV: {:?}", results);
+    debug!("Using ENV: {:?}", secrets.mask_env(&results));
 
     return results;
 }
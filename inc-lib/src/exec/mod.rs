@@ -6,3 +6,7 @@ pub trait Execution<T> {
 
 pub mod executor;
 pub mod system;
+pub mod wait;
+
+#[cfg(test)]
+pub(crate) mod wait_test;
@@ -0,0 +1,242 @@
+use core::config::WaitFor;
+use exec::executor::{
+    execute_command_line, CliError, CommandLine, RunOptions, RunningProcesses, TIMEOUT_EXIT_CODE,
+};
+use libs::duration::parse_duration;
+use libs::shell::build_command_line;
+use std::cmp::{max, min};
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+const DEFAULT_TIMEOUT: &str = "30s";
+const FIRST_DELAY: Duration = Duration::from_millis(100);
+const MAX_DELAY: Duration = Duration::from_secs(2);
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(1);
+
+/**
+ * Something a `wait_for` step, or a service's `ready`, waits for.
+ */
+#[derive(Debug, Clone)]
+pub struct Wait {
+    target: WaitTarget,
+    timeout: (String, Duration),
+}
+
+#[derive(Debug, Clone)]
+enum WaitTarget {
+    Tcp(String),
+    File(PathBuf),
+    Http(String),
+    Command(String, CommandLine),
+}
+
+impl Wait {
+    /**
+     * Relative files are found from `working_dir`, where a `command` would also run.
+     */
+    pub fn new(
+        config: &WaitFor,
+        shell: Option<&str>,
+        working_dir: Option<&Path>,
+    ) -> Result<Self, String> {
+        let target = match (&config.tcp, &config.file, &config.http, &config.command) {
+            (Some(address), None, None, None) => WaitTarget::Tcp(tcp_address(address)?),
+            (None, Some(path), None, None) => {
+                let path = PathBuf::from(path);
+                WaitTarget::File(match working_dir {
+                    Some(dir) if path.is_relative() => dir.join(path),
+                    _ => path,
+                })
+            }
+            (None, None, Some(url), None) => {
+                if !url.starts_with("http://") {
+                    return Err(format!("only http:// URLs can be checked, not {}", url));
+                }
+                WaitTarget::Http(url.clone())
+            }
+            (None, None, None, Some(command)) => {
+                WaitTarget::Command(command.clone(), build_command_line(shell, command)?)
+            }
+            _ => return Err(s!("wait_for needs exactly one of tcp, file, http or command")),
+        };
+
+        let timeout = config.timeout.clone().unwrap_or(s!(DEFAULT_TIMEOUT));
+        return Ok(Wait {
+            target: target,
+            timeout: match parse_duration(&timeout) {
+                Ok(duration) => (timeout, duration),
+                Err(err) => return Err(format!("invalid wait_for timeout, {}", err)),
+            },
+        });
+    }
+
+    /**
+     * Like `localhost:5432 to accept connections`.
+     */
+    pub fn describe(&self) -> String {
+        return match self.target {
+            WaitTarget::Tcp(ref address) => format!("{} to accept connections", address),
+            WaitTarget::File(ref path) => format!("{} to exist", path.to_string_lossy()),
+            WaitTarget::Http(ref url) => format!("{} to return 200", url),
+            WaitTarget::Command(ref command, _) => format!("`{}` to succeed", command),
+        };
+    }
+
+    pub fn timeout(&self) -> &str {
+        return &self.timeout.0;
+    }
+
+    /**
+     * Checks until the target is ready, or `timeout` has passed, like `wait_until`.
+     */
+    pub fn run(
+        &self,
+        extra_env: &HashMap<String, String>,
+        options: &RunOptions,
+        processes: &RunningProcesses,
+    ) -> Result<(), CliError> {
+        return wait_until(&self.describe(), &self.timeout, processes, |deadline| {
+            return Ok(self.is_ready(extra_env, options, processes, deadline));
+        });
+    }
+
+    /**
+     * Checks the target once. A `command` isn't given more time than is left until `deadline`.
+     */
+    pub fn is_ready(
+        &self,
+        extra_env: &HashMap<String, String>,
+        options: &RunOptions,
+        processes: &RunningProcesses,
+        deadline: Instant,
+    ) -> bool {
+        return match self.target {
+            WaitTarget::Tcp(ref address) => port_is_open(address),
+            WaitTarget::File(ref path) => path.exists(),
+            WaitTarget::Http(ref url) => http_status(url) == Ok(200),
+            WaitTarget::Command(_, ref command_line) => {
+                let now = Instant::now();
+                let remaining = if deadline > now {
+                    deadline - now
+                } else {
+                    Duration::from_secs(0)
+                };
+                let options = RunOptions {
+                    quiet: true,
+                    timeout: Some(max(remaining, FIRST_DELAY)),
+                    ..options.clone()
+                };
+                execute_command_line(command_line, extra_env.clone(), &options, processes)
+                    .map_or(false, |code| code == 0)
+            }
+        };
+    }
+}
+
+/**
+ * Calls `is_ready` until it returns true, backing off from 100ms to 2s between calls. Fails
+ * with `TIMEOUT_EXIT_CODE` when `timeout` passes first, or right away when `is_ready` fails.
+ */
+pub fn wait_until<F>(
+    description: &str,
+    timeout: &(String, Duration),
+    processes: &RunningProcesses,
+    mut is_ready: F,
+) -> Result<(), CliError>
+where
+    F: FnMut(Instant) -> Result<bool, CliError>,
+{
+    let deadline = Instant::now() + timeout.1;
+    let mut delay = FIRST_DELAY;
+    loop {
+        if processes.is_stopping() {
+            return Err(CliError::new(1, format!("Stopped waiting for {}", description)));
+        }
+        if is_ready(deadline)? {
+            return Ok(());
+        }
+
+        let now = Instant::now();
+        if now >= deadline {
+            return Err(CliError::new(
+                TIMEOUT_EXIT_CODE,
+                format!("Timed out after {} waiting for {}", timeout.0, description),
+            ));
+        }
+        processes.sleep(min(delay, deadline - now));
+        delay = min(delay * 2, MAX_DELAY);
+    }
+}
+
+/**
+ * Just a port means a port on localhost.
+ */
+fn tcp_address(address: &str) -> Result<String, String> {
+    if address.parse::<u16>().is_ok() {
+        return Ok(format!("localhost:{}", address));
+    }
+    if !address.contains(':') {
+        return Err(format!("`{}` isn't a port, or a host:port", address));
+    }
+
+    return Ok(s!(address));
+}
+
+/**
+ * True when any of the addresses `address` resolves to accepts a connection.
+ */
+pub fn port_is_open(address: &str) -> bool {
+    return match address.to_socket_addrs() {
+        Ok(addresses) => addresses
+            .into_iter()
+            .any(|x| TcpStream::connect_timeout(&x, CONNECT_TIMEOUT).is_ok()),
+        Err(_) => false,
+    };
+}
+
+/**
+ * A plain HTTP/1.0 GET, which is all a health check needs.
+ */
+pub fn http_status(url: &str) -> Result<u16, String> {
+    if !url.starts_with("http://") {
+        return Err(format!("Unable to check {}, only http:// is supported", url));
+    }
+
+    let rest = &url["http://".len()..];
+    let (authority, path) = match rest.find('/') {
+        Some(position) => (&rest[..position], &rest[position..]),
+        None => (rest, "/"),
+    };
+    let address = if authority.contains(':') {
+        s!(authority)
+    } else {
+        format!("{}:80", authority)
+    };
+
+    let addresses = address.to_socket_addrs().map_err(|x| x.to_string())?;
+    let mut stream = addresses
+        .into_iter()
+        .filter_map(|x| TcpStream::connect_timeout(&x, CONNECT_TIMEOUT).ok())
+        .next()
+        .ok_or(format!("Unable to connect to {}", address))?;
+    stream
+        .set_read_timeout(Some(CONNECT_TIMEOUT))
+        .map_err(|x| x.to_string())?;
+    write!(
+        stream,
+        "GET {} HTTP/1.0\r\nHost: {}\r\nConnection: close\r\n\r\n",
+        path, authority
+    ).map_err(|x| x.to_string())?;
+
+    let mut status_line = String::new();
+    BufReader::new(stream)
+        .read_line(&mut status_line)
+        .map_err(|x| x.to_string())?;
+    return match status_line.split_whitespace().nth(1) {
+        Some(status) => status.parse::<u16>().map_err(|x| x.to_string()),
+        None => Err(format!("Invalid response from {}", url)),
+    };
+}
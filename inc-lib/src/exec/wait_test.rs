@@ -0,0 +1,153 @@
+#[cfg(test)]
+pub mod test {
+    use core::config::WaitFor;
+    use exec::executor::{RunOptions, RunningProcesses, TIMEOUT_EXIT_CODE};
+    use exec::wait::*;
+    use libs::secrets::Secrets;
+    use std::collections::HashMap;
+    use std::fs::File;
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+    use std::path::Path;
+    use std::thread;
+
+    fn wait_for() -> WaitFor {
+        return WaitFor {
+            tcp: None,
+            file: None,
+            http: None,
+            command: None,
+            timeout: Some(s!("500ms")),
+        };
+    }
+
+    fn run(wait: &Wait) -> Result<(), i32> {
+        let options = RunOptions {
+            working_dir: None,
+            prefix: None,
+            quiet: true,
+            timeout: None,
+            record_output: false,
+            log_file: None,
+            secrets: Secrets::new(Vec::new()),
+        };
+        return wait
+            .run(&HashMap::new(), &options, &RunningProcesses::new())
+            .map_err(|x| x.code);
+    }
+
+    #[test]
+    fn test_needs_one_target() {
+        assert!(Wait::new(&wait_for(), None, None).is_err());
+        assert!(
+            Wait::new(
+                &WaitFor {
+                    tcp: Some(s!("5432")),
+                    file: Some(s!("ready")),
+                    ..wait_for()
+                },
+                None,
+                None
+            ).is_err()
+        );
+        assert!(
+            Wait::new(
+                &WaitFor {
+                    http: Some(s!("https://localhost/health")),
+                    ..wait_for()
+                },
+                None,
+                None
+            ).is_err()
+        );
+        assert!(
+            Wait::new(
+                &WaitFor {
+                    tcp: Some(s!("localhost")),
+                    ..wait_for()
+                },
+                None,
+                None
+            ).is_err()
+        );
+    }
+
+    #[test]
+    fn test_describe() {
+        let tcp = Wait::new(
+            &WaitFor {
+                tcp: Some(s!("5432")),
+                ..wait_for()
+            },
+            None,
+            None,
+        );
+        assert_eq!(
+            tcp.unwrap().describe(),
+            "localhost:5432 to accept connections"
+        );
+
+        let file = Wait::new(
+            &WaitFor {
+                file: Some(s!("target/ready")),
+                ..wait_for()
+            },
+            None,
+            Some(Path::new("/project")),
+        );
+        assert_eq!(file.unwrap().describe(), "/project/target/ready to exist");
+    }
+
+    #[test]
+    fn test_wait_for_port() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let wait = Wait::new(
+            &WaitFor {
+                tcp: Some(format!("127.0.0.1:{}", address.port())),
+                ..wait_for()
+            },
+            None,
+            None,
+        ).unwrap();
+        assert_eq!(run(&wait), Ok(()));
+
+        drop(listener);
+        assert_eq!(run(&wait), Err(TIMEOUT_EXIT_CODE));
+    }
+
+    #[test]
+    fn test_http_status() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/health", listener.local_addr().unwrap());
+        let server = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request = [0; 512];
+            let read = stream.read(&mut request).unwrap();
+            stream.write_all(b"HTTP/1.0 503 Service Unavailable\r\n\r\n").unwrap();
+            return String::from_utf8_lossy(&request[..read]).into_owned();
+        });
+
+        assert_eq!(http_status(&url), Ok(503));
+        assert!(server.join().unwrap().starts_with("GET /health HTTP/1.0\r\n"));
+    }
+
+    #[test]
+    fn test_wait_for_file() {
+        let dir = ::std::env::temp_dir().join(format!("inc-wait-test-{}", ::std::process::id()));
+        ::std::fs::create_dir_all(&dir).unwrap();
+        let wait = Wait::new(
+            &WaitFor {
+                file: Some(s!("ready")),
+                ..wait_for()
+            },
+            None,
+            Some(&dir),
+        ).unwrap();
+        assert_eq!(run(&wait), Err(TIMEOUT_EXIT_CODE));
+
+        File::create(dir.join("ready")).unwrap();
+        assert_eq!(run(&wait), Ok(()));
+        ::std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -11,6 +11,7 @@ extern crate serde_derive;
 extern crate dirs;
 #[cfg(unix)]
 extern crate libc;
+extern crate serde_json;
 extern crate serde_yaml;
 #[cfg(unix)]
 extern crate signal_hook;
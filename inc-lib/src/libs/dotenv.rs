@@ -0,0 +1,72 @@
+/**
+ * Parses a `.env` file. Each line is `KEY=VALUE`, optionally starting with `export `. Blank
+ * lines and lines starting with `#` are skipped. Values can be single quoted, which are taken
+ * as-is, or double quoted, which understand `\n`, `\"` and `\\`.
+ */
+pub fn parse_env_file(contents: &str) -> Result<Vec<(String, String)>, String> {
+    let mut result: Vec<(String, String)> = Vec::new();
+
+    for (i, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let line = if line.starts_with("export ") {
+            line[7..].trim_start()
+        } else {
+            line
+        };
+
+        let (key, value) = match line.find('=') {
+            Some(position) => (line[..position].trim(), line[position + 1..].trim()),
+            None => return Err(format!("line {} isn't KEY=VALUE", i + 1)),
+        };
+
+        if key.is_empty() || key.contains(char::is_whitespace) {
+            return Err(format!("line {} has an invalid key `{}`", i + 1, key));
+        }
+
+        let value = match parse_value(value) {
+            Some(value) => value,
+            None => return Err(format!("line {} has an unterminated quote", i + 1)),
+        };
+
+        result.push((s!(key), value));
+    }
+
+    return Ok(result);
+}
+
+fn parse_value(value: &str) -> Option<String> {
+    if value.starts_with('\'') {
+        return match value[1..].find('\'') {
+            Some(end) => Some(s!(value[1..end + 1])),
+            None => None,
+        };
+    }
+
+    if value.starts_with('"') {
+        let mut result = String::new();
+        let mut chars = value[1..].chars();
+        while let Some(c) = chars.next() {
+            match c {
+                '"' => return Some(result),
+                '\\' => match chars.next() {
+                    Some('n') => result.push('\n'),
+                    Some(other) => result.push(other),
+                    None => return None,
+                },
+                _ => result.push(c),
+            }
+        }
+        return None;
+    }
+
+    let value = match value.find(" #") {
+        Some(comment) => value[..comment].trim_end(),
+        None => value,
+    };
+
+    return Some(s!(value));
+}
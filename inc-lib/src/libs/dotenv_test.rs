@@ -0,0 +1,34 @@
+#[cfg(test)]
+pub mod test {
+    use libs::dotenv::*;
+
+    #[test]
+    fn test_parse_env_file() {
+        let contents = "# A comment
+FOO=bar
+
+export NAME = World # trailing comment
+SINGLE='a $b \\n c'
+DOUBLE=\"line one\\nline \\\"two\\\"\"
+EMPTY=";
+
+        assert_eq!(
+            parse_env_file(contents),
+            Ok(vec![
+                (s!("FOO"), s!("bar")),
+                (s!("NAME"), s!("World")),
+                (s!("SINGLE"), s!("a $b \\n c")),
+                (s!("DOUBLE"), s!("line one\nline \"two\"")),
+                (s!("EMPTY"), s!("")),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_invalid_env_files() {
+        assert!(parse_env_file("FOO").is_err());
+        assert!(parse_env_file("=bar").is_err());
+        assert!(parse_env_file("FOO BAR=baz").is_err());
+        assert!(parse_env_file("FOO=\"bar").is_err());
+    }
+}
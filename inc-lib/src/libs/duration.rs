@@ -0,0 +1,44 @@
+use std::time::Duration;
+
+/**
+ * Parses durations like `500ms`, `30s`, `10m`, `1h` or `1h30m`. A number without a unit is in
+ * seconds.
+ */
+pub fn parse_duration(value: &str) -> Result<Duration, String> {
+    let value = value.trim();
+    if value.is_empty() {
+        return Err(s!("a duration can't be empty"));
+    }
+
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Ok(Duration::from_secs(seconds));
+    }
+
+    let mut total = Duration::from_secs(0);
+    let mut rest = value;
+    while !rest.is_empty() {
+        let digits = rest.find(|x: char| !x.is_ascii_digit()).unwrap_or(rest.len());
+        if digits == 0 {
+            return Err(format!("`{}` isn't a duration, like 30s or 10m", value));
+        }
+        let amount: u64 = match rest[..digits].parse() {
+            Ok(amount) => amount,
+            Err(_) => return Err(format!("`{}` is too large", value)),
+        };
+        rest = &rest[digits..];
+
+        let unit_length = rest.find(|x: char| x.is_ascii_digit()).unwrap_or(rest.len());
+        let amount = match &rest[..unit_length] {
+            "ms" => Duration::from_millis(amount),
+            "s" => Duration::from_secs(amount),
+            "m" => Duration::from_secs(amount * 60),
+            "h" => Duration::from_secs(amount * 60 * 60),
+            _ => return Err(format!("`{}` isn't a duration, like 30s or 10m", value)),
+        };
+        rest = &rest[unit_length..];
+
+        total += amount;
+    }
+
+    return Ok(total);
+}
@@ -0,0 +1,131 @@
+/**
+ * Expands `${NAME}`, `${NAME:-default}` and `${NAME:?message}` using `lookup`. Names can be
+ * environment variable names, built-ins that start with `inc.`, or matrix values that start
+ * with `matrix.`. An environment variable that isn't found is left for the shell, like
+ * anything else in `${...}`, such as `${#array[@]}`. `$${` is written out as a literal `${`.
+ */
+pub fn interpolate<F>(value: &str, lookup: F) -> Result<String, String>
+where
+    F: Fn(&str) -> Option<String>,
+{
+    return expand(value, &lookup);
+}
+
+fn expand(value: &str, lookup: &dyn Fn(&str) -> Option<String>) -> Result<String, String> {
+    let mut result = String::new();
+    let mut rest = value;
+
+    while let Some(position) = rest.find('$') {
+        result.push_str(&rest[..position]);
+        rest = &rest[position..];
+
+        if rest.starts_with("$${") {
+            result.push_str("${");
+            rest = &rest[3..];
+            continue;
+        }
+
+        if !rest.starts_with("${") {
+            result.push('$');
+            rest = &rest[1..];
+            continue;
+        }
+
+        let end = match closing_brace(rest) {
+            Some(end) => end,
+            None => {
+                result.push_str(rest);
+                rest = "";
+                continue;
+            }
+        };
+
+        let expression = &rest[2..end];
+        let (name, modifier) = match expression.find(':') {
+            Some(split) if expression[split..].starts_with(":-") => (
+                &expression[..split],
+                Some(Modifier::Default(&expression[split + 2..])),
+            ),
+            Some(split) if expression[split..].starts_with(":?") => (
+                &expression[..split],
+                Some(Modifier::Required(&expression[split + 2..])),
+            ),
+            _ => (expression, None),
+        };
+
+        if !is_variable_name(name) {
+            result.push_str(&rest[..end + 1]);
+            rest = &rest[end + 1..];
+            continue;
+        }
+
+        let found = lookup(name).filter(|x| modifier.is_none() || !x.is_empty());
+        match (found, modifier) {
+            (Some(found), _) => result.push_str(&found),
+            (None, Some(Modifier::Default(default))) => {
+                result.push_str(&expand(default, lookup)?)
+            }
+            (None, Some(Modifier::Required(message))) => {
+                return Err(match message {
+                    "" => format!("`${{{}}}` is not set", name),
+                    _ => format!("`${{{}}}` is not set: {}", name, message),
+                });
+            }
+            (None, None) if is_builtin(name) => {
+                return Err(format!(
+                    "`${{{}}}` doesn't exist. Use `$${{{}}}` to leave it for the shell",
+                    name, name
+                ));
+            }
+            (None, None) => result.push_str(&rest[..end + 1]),
+        }
+        rest = &rest[end + 1..];
+    }
+
+    result.push_str(rest);
+    return Ok(result);
+}
+
+enum Modifier<'a> {
+    Default(&'a str),
+    Required(&'a str),
+}
+
+/**
+ * Where the `}` that closes the `${` at the start of `value` is, skipping over any nested ones.
+ */
+fn closing_brace(value: &str) -> Option<usize> {
+    let mut depth = 0;
+    for (position, c) in value.char_indices().skip(1) {
+        match c {
+            '{' => depth += 1,
+            '}' if depth == 1 => return Some(position),
+            '}' => depth -= 1,
+            _ => {}
+        }
+    }
+
+    return None;
+}
+
+fn is_builtin(name: &str) -> bool {
+    return name.starts_with("inc.") || name.starts_with("matrix.");
+}
+
+fn is_variable_name(name: &str) -> bool {
+    let name = if name.starts_with("inc.") {
+        &name[4..]
+    } else if name.starts_with("matrix.") {
+        &name[7..]
+    } else {
+        name
+    };
+
+    let mut chars = name.chars();
+    return match chars.next() {
+        Some(c) if c.is_ascii_alphabetic() || c == '_' => {
+            chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+        }
+        _ => false,
+    };
+}
@@ -0,0 +1,85 @@
+#[cfg(test)]
+pub mod test {
+    use libs::interpolate::*;
+
+    fn lookup(name: &str) -> Option<String> {
+        return match name {
+            "NAME" => Some(s!("World")),
+            "EMPTY" => Some(s!("")),
+            "inc.command" => Some(s!("build")),
+            "matrix.target" => Some(s!("x86_64-apple-darwin")),
+            _ => None,
+        };
+    }
+
+    #[test]
+    fn test_variables_are_expanded() {
+        assert_eq!(
+            interpolate("Hello ${NAME} from ${inc.command}", lookup),
+            Ok(s!("Hello World from build"))
+        );
+        assert_eq!(interpolate("${EMPTY}", lookup), Ok(s!("")));
+        assert_eq!(
+            interpolate("--target=${matrix.target}", lookup),
+            Ok(s!("--target=x86_64-apple-darwin"))
+        );
+    }
+
+    #[test]
+    fn test_defaults() {
+        assert_eq!(interpolate("${MISSING:-none}", lookup), Ok(s!("none")));
+        assert_eq!(interpolate("${EMPTY:-none}", lookup), Ok(s!("none")));
+        assert_eq!(interpolate("${NAME:-none}", lookup), Ok(s!("World")));
+        assert_eq!(interpolate("${MISSING:-}", lookup), Ok(s!("")));
+    }
+
+    #[test]
+    fn test_nested_defaults() {
+        assert_eq!(interpolate("${MISSING:-${NAME}}!", lookup), Ok(s!("World!")));
+        assert_eq!(
+            interpolate("${MISSING:-${OTHER:-none}}", lookup),
+            Ok(s!("none"))
+        );
+        assert_eq!(interpolate("${NAME:-${inc.missing}}", lookup), Ok(s!("World")));
+        assert_eq!(interpolate("${MISSING:-${f}}", lookup), Ok(s!("${f}")));
+    }
+
+    #[test]
+    fn test_shell_syntax_is_left_alone() {
+        assert_eq!(
+            interpolate("echo $NAME ${#parts[@]} $$ $", lookup),
+            Ok(s!("echo $NAME ${#parts[@]} $$ $"))
+        );
+        assert_eq!(
+            interpolate("echo $${NAME} ${NAME", lookup),
+            Ok(s!("echo ${NAME} ${NAME"))
+        );
+        assert_eq!(
+            interpolate("echo ${parts[0]:0:2} ${#parts[@]}", lookup),
+            Ok(s!("echo ${parts[0]:0:2} ${#parts[@]}"))
+        );
+    }
+
+    #[test]
+    fn test_unknown_variables_are_left_for_the_shell() {
+        assert_eq!(
+            interpolate("for f in *; do echo ${f} ${PWD}; done", lookup),
+            Ok(s!("for f in *; do echo ${f} ${PWD}; done"))
+        );
+    }
+
+    #[test]
+    fn test_missing_variables_fail() {
+        assert_eq!(
+            interpolate("${MISSING:?}", lookup),
+            Err(s!("`${MISSING}` is not set"))
+        );
+        assert_eq!(
+            interpolate("${EMPTY:?set it first}", lookup),
+            Err(s!("`${EMPTY}` is not set: set it first"))
+        );
+        assert_eq!(interpolate("${NAME:?}", lookup), Ok(s!("World")));
+        assert!(interpolate("${inc.missing}", lookup).is_err());
+        assert!(interpolate("${matrix.missing}", lookup).is_err());
+    }
+}
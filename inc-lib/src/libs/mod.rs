@@ -1,3 +1,20 @@
+pub mod dotenv;
+pub mod duration;
+pub mod interpolate;
+pub mod output;
 #[macro_use]
 pub mod process;
 pub mod scm;
+pub mod secrets;
+pub mod shell;
+
+#[cfg(test)]
+pub(crate) mod dotenv_test;
+#[cfg(test)]
+pub(crate) mod duration_test;
+#[cfg(test)]
+pub(crate) mod interpolate_test;
+#[cfg(test)]
+pub(crate) mod secrets_test;
+#[cfg(test)]
+pub(crate) mod shell_test;
@@ -0,0 +1,33 @@
+use serde::Serialize;
+use serde_json;
+use serde_yaml;
+
+/**
+ * How a listing is written. `Text` is for people, the others have a stable schema for tools.
+ */
+#[derive(Debug, Clone, PartialEq)]
+pub enum OutputFormat {
+    Text,
+    Json,
+    Yaml,
+}
+
+impl OutputFormat {
+    pub fn from_name(name: Option<&str>) -> Self {
+        return match name {
+            Some("json") => OutputFormat::Json,
+            Some("yaml") => OutputFormat::Yaml,
+            _ => OutputFormat::Text,
+        };
+    }
+}
+
+pub fn serialize<T: Serialize>(value: &T, format: &OutputFormat) -> Result<String, String> {
+    let result = match format {
+        OutputFormat::Json => serde_json::to_string_pretty(value).map_err(|x| x.to_string()),
+        OutputFormat::Yaml => serde_yaml::to_string(value).map_err(|x| x.to_string()),
+        OutputFormat::Text => Err(s!("text isn't a structured format")),
+    };
+
+    return result.map_err(|err| format!("Unable to write the output: {}", err));
+}
@@ -0,0 +1,69 @@
+use std::collections::HashMap;
+
+/**
+ * What secret values are replaced with.
+ */
+pub const MASK: &str = "***";
+
+/**
+ * The values of secret environment variables, which are masked anywhere inc shows them.
+ */
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Secrets {
+    values: Vec<String>,
+}
+
+impl Secrets {
+    /**
+     * Empty values are left out, because there is nothing to hide. The longest values are
+     * masked first, so a secret that contains another one is still masked completely.
+     */
+    pub fn new<I: IntoIterator<Item = String>>(values: I) -> Self {
+        let mut values: Vec<String> = values.into_iter().filter(|x| !x.is_empty()).collect();
+        values.sort_by(|a, b| b.len().cmp(&a.len()).then(a.cmp(b)));
+        values.dedup();
+
+        return Secrets { values: values };
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self.values.is_empty();
+    }
+
+    /**
+     * Adds the values from `other`.
+     */
+    pub fn merge(&self, other: &Secrets) -> Secrets {
+        return Secrets::new(self.values.iter().chain(other.values.iter()).cloned());
+    }
+
+    /**
+     * True when any of the values is part of one of the `texts`.
+     */
+    pub fn appear_in<'a, I: IntoIterator<Item = &'a str>>(&self, texts: I) -> bool {
+        return texts
+            .into_iter()
+            .any(|text| self.values.iter().any(|value| text.contains(value.as_str())));
+    }
+
+    pub fn mask(&self, text: &str) -> String {
+        let mut result = s!(text);
+        for value in self.values.iter() {
+            if result.contains(value.as_str()) {
+                result = result.replace(value.as_str(), MASK);
+            }
+        }
+
+        return result;
+    }
+
+    /**
+     * A copy of `env` that is safe to log.
+     */
+    pub fn mask_env(&self, env: &HashMap<String, String>) -> HashMap<String, String> {
+        return env
+            .iter()
+            .map(|(key, value)| (key.clone(), self.mask(value)))
+            .collect();
+    }
+}
@@ -0,0 +1,42 @@
+#[cfg(test)]
+pub mod test {
+    use libs::secrets::*;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_mask() {
+        let secrets = Secrets::new(vec![s!("token"), s!(""), s!("my-token-value"), s!("token")]);
+        assert_eq!(secrets.mask("using my-token-value"), "using ***");
+        assert_eq!(secrets.mask("token=token"), "***=***");
+        assert_eq!(secrets.mask("nothing secret"), "nothing secret");
+
+        assert!(Secrets::new(vec![s!("")]).is_empty());
+        assert_eq!(Secrets::default().mask("token"), "token");
+    }
+
+    #[test]
+    fn test_mask_env() {
+        let secrets = Secrets::new(vec![s!("hunter2")]);
+        let mut env: HashMap<String, String> = HashMap::new();
+        env.insert(s!("PASSWORD"), s!("hunter2"));
+        env.insert(s!("USER"), s!("admin"));
+
+        let masked = secrets.mask_env(&env);
+        assert_eq!(masked.get("PASSWORD"), Some(&s!("***")));
+        assert_eq!(masked.get("USER"), Some(&s!("admin")));
+    }
+
+    #[test]
+    fn test_appear_in() {
+        let secrets = Secrets::new(vec![s!("hunter2")]);
+        assert!(secrets.appear_in(vec!["echo", "login --password hunter2"]));
+        assert!(!secrets.appear_in(vec!["echo", "login"]));
+        assert!(!Secrets::default().appear_in(vec!["hunter2"]));
+    }
+
+    #[test]
+    fn test_merge() {
+        let secrets = Secrets::new(vec![s!("a-secret")]).merge(&Secrets::new(vec![s!("other")]));
+        assert_eq!(secrets.mask("a-secret and other"), "*** and ***");
+    }
+}
@@ -0,0 +1,196 @@
+use exec::executor::CommandLine;
+use std::path::{Path, PathBuf};
+
+pub const NO_SHELL: &'static str = "none";
+
+#[cfg(unix)]
+const DEFAULT_SCRIPT_SHELL: &'static str = "bash";
+#[cfg(windows)]
+const DEFAULT_SCRIPT_SHELL: &'static str = "cmd";
+
+/**
+ * Quotes the argument so a POSIX shell will see it as a single word.
+ */
+pub fn quote_arg(arg: &str) -> String {
+    let is_safe = !arg.is_empty() && arg
+        .chars()
+        .all(|c| c.is_alphanumeric() || "-_=./:,@%+".contains(c));
+    if is_safe {
+        return s!(arg);
+    }
+
+    return format!("'{}'", arg.replace("'", "'\\''"));
+}
+
+pub fn join_args(args: &[String]) -> String {
+    let quoted: Vec<String> = args.iter().map(|x| quote_arg(x)).collect();
+    return quoted.join(" ");
+}
+
+/**
+ * Splits the string into words following the POSIX shell quoting rules. Nothing is expanded,
+ * so `$HOME` stays `$HOME`.
+ */
+pub fn split_words(line: &str) -> Result<Vec<String>, String> {
+    let mut words: Vec<String> = Vec::new();
+    let mut word = String::new();
+    let mut in_word = false;
+    let mut chars = line.chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\'' => {
+                in_word = true;
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(c) => word.push(c),
+                        None => return Err(format!("Unterminated single quote in `{}`", line)),
+                    }
+                }
+            }
+            '"' => {
+                in_word = true;
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some('\n') => {}
+                            Some(c) if "$`\"\\".contains(c) => word.push(c),
+                            Some(c) => {
+                                word.push('\\');
+                                word.push(c);
+                            }
+                            None => return Err(format!("Unterminated double quote in `{}`", line)),
+                        },
+                        Some(c) => word.push(c),
+                        None => return Err(format!("Unterminated double quote in `{}`", line)),
+                    }
+                }
+            }
+            '\\' => {
+                in_word = true;
+                match chars.next() {
+                    Some('\n') => {}
+                    Some(c) => word.push(c),
+                    None => word.push('\\'),
+                }
+            }
+            c if c.is_whitespace() => {
+                if in_word {
+                    words.push(word);
+                    word = String::new();
+                    in_word = false;
+                }
+            }
+            c => {
+                in_word = true;
+                word.push(c);
+            }
+        }
+    }
+
+    if in_word {
+        words.push(word);
+    }
+
+    return Ok(words);
+}
+
+/**
+ * Builds the command line that runs `command` with the given shell. Well known shells and
+ * interpreters get the flag they need to run a string. Anything with spaces in it is used as
+ * written, with the command added as the last argument. `none` runs the command directly.
+ */
+pub fn build_command_line(shell: Option<&str>, command: &str) -> Result<CommandLine, String> {
+    let shell = match shell {
+        Some(value) => value.trim(),
+        None => return Ok(CommandLine::Shell(s!(command))),
+    };
+
+    let mut words = if shell == NO_SHELL {
+        match split_words(command) {
+            Ok(words) => words,
+            Err(err) => return Err(err),
+        }
+    } else {
+        let mut words = match split_words(shell) {
+            Ok(words) => words,
+            Err(err) => return Err(err),
+        };
+        if words.len() == 1 {
+            let flag = default_shell_flag(&words[0]);
+            words.push(s!(flag));
+        }
+        words.push(s!(command));
+        words
+    };
+
+    if words.is_empty() {
+        return Err(format!("Unable to find a command to run in `{}`", command));
+    }
+
+    let program = words.remove(0);
+    return Ok(CommandLine::Direct(PathBuf::from(program), words));
+}
+
+fn default_shell_flag(shell: &str) -> &'static str {
+    return match shell_name(shell) {
+        "node" => "-e",
+        "cmd" => "/C",
+        "powershell" | "pwsh" => "-Command",
+        _ => "-c",
+    };
+}
+
+/**
+ * Builds the command line that runs a multi-line script. The script gets written to a file
+ * which is given to the shell, followed by the `script_args`. Shells from the `sh` family
+ * run the script under `set -euo pipefail`.
+ */
+pub fn build_script_command_line(
+    shell: Option<&str>,
+    script: &str,
+    script_args: &[String],
+) -> Result<CommandLine, String> {
+    let shell = shell.map(|x| x.trim()).unwrap_or(DEFAULT_SCRIPT_SHELL);
+    if shell == NO_SHELL {
+        return Err(s!("a script needs a shell to run it, so it can't use `none`"));
+    }
+
+    let mut words = match split_words(shell) {
+        Ok(words) => words,
+        Err(err) => return Err(err),
+    };
+    if words.is_empty() {
+        return Err(s!("a script needs a shell to run it"));
+    }
+    if words.len() == 1 && shell_name(&words[0]) == "cmd" {
+        words.push(s!("/C"));
+    }
+
+    let program = words.remove(0);
+    let contents = format!("{}{}", script_preamble(&program), script);
+
+    return Ok(CommandLine::Script {
+        interpreter: PathBuf::from(program),
+        interpreter_args: words,
+        contents: contents,
+        script_args: script_args.to_vec(),
+    });
+}
+
+fn script_preamble(program: &str) -> &'static str {
+    return match shell_name(program) {
+        "bash" | "zsh" | "ksh" => "set -euo pipefail\n",
+        "sh" | "dash" | "ash" => "set -eu\n(set -o pipefail) 2>/dev/null && set -o pipefail\n",
+        _ => "",
+    };
+}
+
+fn shell_name(shell: &str) -> &str {
+    return Path::new(shell)
+        .file_stem()
+        .and_then(|x| x.to_str())
+        .unwrap_or(shell);
+}
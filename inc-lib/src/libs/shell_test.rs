@@ -0,0 +1,82 @@
+#[cfg(test)]
+pub mod test {
+    use exec::executor::CommandLine;
+    use libs::shell::*;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_safe_args_are_not_quoted() {
+        assert_eq!(quote_arg("--nocapture"), "--nocapture");
+        assert_eq!(quote_arg("./target/debug"), "./target/debug");
+    }
+
+    #[test]
+    fn test_unsafe_args_are_quoted() {
+        assert_eq!(quote_arg(""), "''");
+        assert_eq!(quote_arg("two words"), "'two words'");
+        assert_eq!(quote_arg("$HOME"), "'$HOME'");
+        assert_eq!(quote_arg("it's"), "'it'\\''s'");
+    }
+
+    #[test]
+    fn test_join_args() {
+        assert_eq!(
+            join_args(&vec![s!("ls"), s!("my dir"), s!("-l")]),
+            "ls 'my dir' -l"
+        );
+    }
+
+    #[test]
+    fn test_split_words() {
+        assert_eq!(
+            split_words("echo  \"two  spaces\" 'it''s' a\\ b $HOME"),
+            Ok(vec![
+                s!("echo"),
+                s!("two  spaces"),
+                s!("its"),
+                s!("a b"),
+                s!("$HOME"),
+            ])
+        );
+        assert_eq!(
+            split_words("echo \"say \\\"hi\\\"\" ''"),
+            Ok(vec![s!("echo"), s!("say \"hi\""), s!("")])
+        );
+        assert!(split_words("echo 'open").is_err());
+        assert!(split_words("echo \"open").is_err());
+    }
+
+    #[test]
+    fn test_build_command_line() {
+        assert_eq!(
+            build_command_line(None, "echo hi"),
+            Ok(CommandLine::Shell(s!("echo hi")))
+        );
+        assert_eq!(
+            build_command_line(Some("bash"), "echo hi"),
+            Ok(CommandLine::Direct(
+                PathBuf::from("bash"),
+                vec![s!("-c"), s!("echo hi")]
+            ))
+        );
+        assert_eq!(
+            build_command_line(Some("node"), "console.log(1)"),
+            Ok(CommandLine::Direct(
+                PathBuf::from("node"),
+                vec![s!("-e"), s!("console.log(1)")]
+            ))
+        );
+        assert_eq!(
+            build_command_line(Some("bash -euo pipefail -c"), "echo hi"),
+            Ok(CommandLine::Direct(
+                PathBuf::from("bash"),
+                vec![s!("-euo"), s!("pipefail"), s!("-c"), s!("echo hi")]
+            ))
+        );
+        assert_eq!(
+            build_command_line(Some("none"), "ls 'my dir'"),
+            Ok(CommandLine::Direct(PathBuf::from("ls"), vec![s!("my dir")]))
+        );
+        assert!(build_command_line(Some("none"), "  ").is_err());
+    }
+}
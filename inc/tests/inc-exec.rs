@@ -6,7 +6,15 @@ mod shared;
 #[cfg(test)]
 #[cfg(unix)]
 mod exec_integration {
+    use assert_cli::Environment;
     use shared::utils::*;
+    use std::fs;
+    use std::io::Read;
+    use std::os::unix::process::CommandExt;
+    use std::path::Path;
+    use std::process::{Command, Stdio};
+    use std::thread::sleep;
+    use std::time::{Duration, Instant};
 
     #[test]
     fn with_no_args() {
@@ -18,17 +26,34 @@ mod exec_integration {
             .contains(
                 "
 USAGE:
-    inc exec [FLAGS] <command>
+    inc exec [FLAGS] [OPTIONS] <command>... [-- <args>...]
 
 FLAGS:
+        --clear            Clear the screen before every run, when watching.
+        --dry-run          Show what would run, without running anything.
+        --force            Run commands even when their inputs and outputs haven't changed.
     -h, --help             Prints help information
+        --keep-going       Keep running the steps that don't depend on a failed step.
         --list-commands    List all of the avaliable commands.
     -q, --quite            Only error output will be displayed
+        --report-output    Include the output of every step in the report.
     -v, --verbose          Increasing verbosity
     -w, --warn             Only display warning messages
+        --watch            Run the command again every time a watched file changes.
+
+OPTIONS:
+        --env <env>...                     Set an environment variable for every step, as KEY=VALUE.
+        --format <format>                  How to write the list, text by default. [possible values: text, json, yaml]
+    -j, --jobs <jobs>                      Number of steps to run at the same time.
+        --log-dir <DIR>                    Also write the output of every step to a file in DIR. Defaults to the log_dir
+                                           from inc.yaml.
+        --report <PATH>                    Write what ran, and how long it took, to a file.
+        --report-format <report-format>    Format of the report, JUnit XML when the path ends in .xml, otherwise JSON.
+                                           [possible values: json, junit]
 
 ARGS:
-    <command>    Name of the command to execute.",
+    <command>...    Names of the commands to execute, in order.
+    <args>...       Extra arguments passed to the last command, after `--`.",
             ).unwrap();
     }
 
@@ -175,4 +200,1468 @@ Goodbye World!
                 .unwrap();
         });
     }
+
+    #[test]
+    fn extra_args_are_appended_to_last_command() {
+        with_test_dir(|tmp_dir| {
+            let file_path = tmp_dir.clone().join("inc.yaml");
+            copy_resource("sample4.yaml", file_path);
+
+            create_assert()
+                .with_args(&["exec", "greet", "--", "world", "two words"])
+                .current_dir(tmp_dir.clone())
+                .succeeds()
+                .and()
+                .stderr()
+                .is("")
+                .stdout()
+                .contains(
+                    "** Executing `echo \"Hello\"`
+Hello
+** Executing `echo \"Goodbye\" world 'two words'`
+Goodbye world two words
+",
+                ).unwrap();
+        });
+    }
+
+    #[test]
+    fn extra_args_replace_placeholder() {
+        with_test_dir(|tmp_dir| {
+            let file_path = tmp_dir.clone().join("inc.yaml");
+            copy_resource("sample4.yaml", file_path);
+
+            create_assert()
+                .with_args(&["exec", "placeholder", "--", "--flag"])
+                .current_dir(tmp_dir.clone())
+                .succeeds()
+                .and()
+                .stderr()
+                .is("")
+                .stdout()
+                .contains(
+                    "before --flag after
+** Executing `echo \"Goodbye\"`
+Goodbye
+",
+                ).unwrap();
+        });
+    }
+
+    #[test]
+    fn when_failures_are_ignored_it_will_continue() {
+        with_test_dir(|tmp_dir| {
+            let file_path = tmp_dir.clone().join("inc.yaml");
+            copy_resource("sample5.yaml", file_path);
+
+            create_assert()
+                .with_args(&["exec", "build"])
+                .current_dir(tmp_dir.clone())
+                .succeeds()
+                .and()
+                .stderr()
+                .is("")
+                .stdout()
+                .contains(
+                    "Goodbye World!
+Failed commands:
+ - `false` returned 1",
+                ).unwrap();
+
+            create_assert()
+                .with_args(&["exec", "strict"])
+                .current_dir(tmp_dir.clone())
+                .fails_with(1)
+                .and()
+                .stdout()
+                .contains("Goodbye World!\nFailed commands:")
+                .unwrap();
+        });
+    }
+
+    #[test]
+    fn when_step_failure_is_ignored_only_that_step_continues() {
+        with_test_dir(|tmp_dir| {
+            let file_path = tmp_dir.clone().join("inc.yaml");
+            copy_resource("sample5.yaml", file_path);
+
+            create_assert()
+                .with_args(&["exec", "step"])
+                .current_dir(tmp_dir.clone())
+                .fails_with(1)
+                .and()
+                .stderr()
+                .is("Command: `false` returned 1")
+                .stdout()
+                .doesnt_contain("Never Printed")
+                .unwrap();
+
+            create_assert()
+                .with_args(&["exec", "lenient-step"])
+                .current_dir(tmp_dir.clone())
+                .succeeds()
+                .and()
+                .stdout()
+                .contains("Goodbye World!\nFailed commands:\n - `false` returned 1")
+                .unwrap();
+        });
+    }
+
+    #[test]
+    fn dependencies_run_before_the_command() {
+        with_test_dir(|tmp_dir| {
+            let file_path = tmp_dir.clone().join("inc.yaml");
+            copy_resource("sample6.yaml", file_path);
+
+            create_assert()
+                .with_args(&["exec", "ci"])
+                .current_dir(tmp_dir.clone())
+                .succeeds()
+                .and()
+                .stderr()
+                .is("")
+                .stdout()
+                .contains(
+                    "*** Running `build`
+Building
+*** Running `lint`
+Linting
+*** Running `test`
+Testing
+*** Running `ci`
+",
+                ).unwrap();
+        });
+    }
+
+    #[test]
+    fn dependency_cycles_fail() {
+        with_test_dir(|tmp_dir| {
+            let file_path = tmp_dir.clone().join("inc.yaml");
+            copy_resource("sample6.yaml", file_path);
+
+            create_assert()
+                .with_args(&["exec", "loop-a"])
+                .current_dir(tmp_dir.clone())
+                .fails_with(2)
+                .and()
+                .stderr()
+                .is("Found a dependency cycle: loop-a -> loop-b -> loop-a! Failing!")
+                .unwrap();
+        });
+    }
+
+    #[test]
+    fn multiple_commands_run_in_order() {
+        with_test_dir(|tmp_dir| {
+            let file_path = tmp_dir.clone().join("inc.yaml");
+            copy_resource("sample6.yaml", file_path);
+
+            create_assert()
+                .with_args(&["exec", "test", "lint", "--", "--all"])
+                .current_dir(tmp_dir.clone())
+                .succeeds()
+                .and()
+                .stdout()
+                .contains(
+                    "*** Running `build`
+Building
+*** Running `test`
+Testing
+*** Running `lint`
+Linting --all",
+                ).unwrap();
+        });
+    }
+
+    #[test]
+    fn multiple_commands_stop_at_the_first_failure() {
+        with_test_dir(|tmp_dir| {
+            let file_path = tmp_dir.clone().join("inc.yaml");
+            copy_resource("sample7.yaml", file_path);
+
+            create_assert()
+                .with_args(&["exec", "broken", "fast"])
+                .current_dir(tmp_dir.clone())
+                .fails_with(1)
+                .and()
+                .stdout()
+                .doesnt_contain("Fast Dependency")
+                .unwrap();
+
+            create_assert()
+                .with_args(&["exec", "--keep-going", "broken", "fast"])
+                .current_dir(tmp_dir.clone())
+                .fails_with(1)
+                .and()
+                .stdout()
+                .contains("Fast Dependency")
+                .unwrap();
+        });
+    }
+
+    #[test]
+    fn multiple_commands_run_in_order_in_parallel() {
+        with_test_dir(|tmp_dir| {
+            let file_path = tmp_dir.clone().join("inc.yaml");
+            copy_resource("sample7.yaml", file_path);
+
+            create_assert()
+                .with_args(&["exec", "-j", "2", "slow", "fast"])
+                .current_dir(tmp_dir.clone())
+                .succeeds()
+                .and()
+                .stdout()
+                .contains("[slow] Slow Dependency\n*** Running `fast`\n[fast] Fast Dependency")
+                .unwrap();
+        });
+    }
+
+    #[test]
+    fn parallel_steps_are_prefixed() {
+        with_test_dir(|tmp_dir| {
+            let file_path = tmp_dir.clone().join("inc.yaml");
+            copy_resource("sample7.yaml", file_path);
+
+            create_assert()
+                .with_args(&["exec", "-j", "2", "checks"])
+                .current_dir(tmp_dir.clone())
+                .succeeds()
+                .and()
+                .stderr()
+                .is("")
+                .stdout()
+                .contains("[checks:2] Fast Check\n[checks:1] Slow Check")
+                .unwrap();
+        });
+    }
+
+    #[test]
+    fn parallel_steps_stop_when_one_fails() {
+        with_test_dir(|tmp_dir| {
+            let file_path = tmp_dir.clone().join("inc.yaml");
+            copy_resource("sample7.yaml", file_path);
+
+            create_assert()
+                .with_args(&["exec", "-j", "2", "fail-fast"])
+                .current_dir(tmp_dir.clone())
+                .fails_with(1)
+                .and()
+                .stderr()
+                .is("Command: `false` returned 1")
+                .stdout()
+                .doesnt_contain("[fail-fast:1] Never Printed")
+                .unwrap();
+        });
+    }
+
+    #[test]
+    fn keep_going_runs_independent_commands() {
+        with_test_dir(|tmp_dir| {
+            let file_path = tmp_dir.clone().join("inc.yaml");
+            copy_resource("sample7.yaml", file_path);
+
+            create_assert()
+                .with_args(&["exec", "--keep-going", "keep-going"])
+                .current_dir(tmp_dir.clone())
+                .fails_with(1)
+                .and()
+                .stdout()
+                .contains(
+                    "Fast Dependency
+Failed commands:
+ - `false` returned 1
+Skipped `keep-going` because a dependency failed",
+                ).unwrap();
+        });
+    }
+
+    #[test]
+    fn shell_commands_are_not_split() {
+        with_test_dir(|tmp_dir| {
+            let file_path = tmp_dir.clone().join("inc.yaml");
+            copy_resource("sample8.yaml", file_path);
+
+            create_assert()
+                .with_args(&["exec", "quoted"])
+                .current_dir(tmp_dir.clone())
+                .succeeds()
+                .and()
+                .stdout()
+                .is("two  spaces single quoted")
+                .unwrap();
+        });
+    }
+
+    #[test]
+    fn argv_commands_skip_the_shell() {
+        with_test_dir(|tmp_dir| {
+            let file_path = tmp_dir.clone().join("inc.yaml");
+            copy_resource("sample8.yaml", file_path);
+
+            create_assert()
+                .with_args(&["exec", "argv"])
+                .current_dir(tmp_dir.clone())
+                .succeeds()
+                .and()
+                .stdout()
+                .is("my dir/file.txt $HOME")
+                .unwrap();
+
+            create_assert()
+                .with_args(&["exec", "argv-args", "--", "a", "b c"])
+                .current_dir(tmp_dir.clone())
+                .succeeds()
+                .and()
+                .stdout()
+                .is("<a><b c>")
+                .unwrap();
+        });
+    }
+
+    #[test]
+    fn commands_use_the_configured_shell() {
+        with_test_dir(|tmp_dir| {
+            let file_path = tmp_dir.clone().join("inc.yaml");
+            copy_resource("sample9.yaml", file_path);
+
+            create_assert()
+                .with_args(&["exec", "herestring"])
+                .current_dir(tmp_dir.clone())
+                .succeeds()
+                .and()
+                .stdout()
+                .is("3 parts")
+                .unwrap();
+
+            create_assert()
+                .with_args(&["exec", "direct"])
+                .current_dir(tmp_dir.clone())
+                .succeeds()
+                .and()
+                .stdout()
+                .is("$HOME two  words")
+                .unwrap();
+        });
+    }
+
+    #[test]
+    fn scripts_keep_shell_state_between_lines() {
+        with_test_dir(|tmp_dir| {
+            let file_path = tmp_dir.clone().join("inc.yaml");
+            copy_resource("sample10.yaml", file_path);
+
+            create_assert()
+                .with_args(&["exec", "setup"])
+                .current_dir(tmp_dir.clone())
+                .succeeds()
+                .and()
+                .stdout()
+                .is("Hello World from dir")
+                .unwrap();
+
+            create_assert()
+                .with_args(&["exec", "args", "--", "a b", "c"])
+                .current_dir(tmp_dir.clone())
+                .succeeds()
+                .and()
+                .stdout()
+                .is("Got 2 args: a b")
+                .unwrap();
+        });
+    }
+
+    #[test]
+    fn scripts_stop_on_pipe_failures() {
+        with_test_dir(|tmp_dir| {
+            let file_path = tmp_dir.clone().join("inc.yaml");
+            copy_resource("sample10.yaml", file_path);
+
+            create_assert()
+                .with_args(&["exec", "strict"])
+                .current_dir(tmp_dir.clone())
+                .fails_with(1)
+                .and()
+                .stderr()
+                .is("Command: `echo \"Before\" ...` returned 1")
+                .stdout()
+                .is("Before")
+                .unwrap();
+        });
+    }
+
+    #[test]
+    fn commands_run_in_their_working_dir() {
+        with_test_dir(|tmp_dir| {
+            let file_path = tmp_dir.clone().join("inc.yaml");
+            copy_resource("sample11.yaml", file_path);
+
+            let sub_dir = tmp_dir.clone().join("sub");
+            fs::create_dir(&sub_dir).unwrap();
+            fs::write(tmp_dir.join("marker.txt"), "From The Root\n").unwrap();
+            fs::write(sub_dir.join("inner.txt"), "From Sub\n").unwrap();
+
+            create_assert()
+                .with_args(&["exec", "root"])
+                .current_dir(sub_dir.clone())
+                .succeeds()
+                .and()
+                .stdout()
+                .is("From The Root")
+                .unwrap();
+
+            create_assert()
+                .with_args(&["exec", "sub"])
+                .current_dir(tmp_dir.clone())
+                .succeeds()
+                .and()
+                .stdout()
+                .contains("From Sub\n** Executing `cat marker.txt`\nFrom The Root")
+                .unwrap();
+
+            create_assert()
+                .with_args(&["exec", "missing"])
+                .current_dir(tmp_dir.clone())
+                .fails()
+                .and()
+                .stderr()
+                .contains("does-not-exist\" doesn't exist")
+                .unwrap();
+        });
+    }
+
+    #[test]
+    fn variables_are_expanded() {
+        with_test_dir(|tmp_dir| {
+            let file_path = tmp_dir.clone().join("inc.yaml");
+            copy_resource("sample12.yaml", file_path);
+            fs::write(tmp_dir.join("marker.txt"), "From The Project\n").unwrap();
+
+            create_assert()
+                .with_args(&["exec", "greet"])
+                .current_dir(tmp_dir.clone())
+                .succeeds()
+                .and()
+                .stdout()
+                .contains("Hello World from greet")
+                .stdout()
+                .contains("Hi there")
+                .unwrap();
+
+            create_assert()
+                .with_args(&["exec", "greet"])
+                .with_env(Environment::inherit().insert("INC_TEST_NAME", "Inc"))
+                .current_dir(tmp_dir.clone())
+                .succeeds()
+                .and()
+                .stdout()
+                .contains("Hello Inc from greet")
+                .stdout()
+                .contains("Hi Inc")
+                .unwrap();
+
+            create_assert()
+                .with_args(&["exec", "project-dir"])
+                .current_dir(tmp_dir.clone())
+                .succeeds()
+                .and()
+                .stdout()
+                .is("From The Project")
+                .unwrap();
+
+            create_assert()
+                .with_args(&["exec", "escaped"])
+                .current_dir(tmp_dir.clone())
+                .succeeds()
+                .and()
+                .stdout()
+                .is("bar")
+                .unwrap();
+        });
+    }
+
+    #[test]
+    fn missing_variables_fail_before_running() {
+        with_test_dir(|tmp_dir| {
+            let file_path = tmp_dir.clone().join("inc.yaml");
+            copy_resource("sample12.yaml", file_path);
+
+            create_assert()
+                .with_args(&["exec", "required"])
+                .current_dir(tmp_dir.clone())
+                .fails()
+                .and()
+                .stdout()
+                .doesnt_contain("Never Printed")
+                .stderr()
+                .contains("`${INC_TEST_REQUIRED}` is not set")
+                .unwrap();
+
+            create_assert()
+                .with_args(&["exec", "shell-local"])
+                .current_dir(tmp_dir.clone())
+                .succeeds()
+                .and()
+                .stdout()
+                .is("a\nb")
+                .unwrap();
+        });
+    }
+
+    #[test]
+    fn env_is_layered() {
+        with_test_dir(|tmp_dir| {
+            let file_path = tmp_dir.clone().join("inc.yaml");
+            copy_resource("sample13.yaml", file_path);
+            fs::write(tmp_dir.join(".env"), "FROM_FILE=file\nPROJECT=file\n").unwrap();
+
+            create_assert()
+                .with_args(&["exec", "show"])
+                .current_dir(tmp_dir.clone())
+                .succeeds()
+                .and()
+                .stdout()
+                .contains("file project command command\n")
+                .stdout()
+                .contains("\nstep")
+                .unwrap();
+
+            create_assert()
+                .with_args(&["exec", "--env", "SHARED=cli", "show"])
+                .current_dir(tmp_dir.clone())
+                .succeeds()
+                .and()
+                .stdout()
+                .contains("file project command cli\n")
+                .stdout()
+                .contains("\ncli")
+                .unwrap();
+
+            create_assert()
+                .with_args(&["exec", "--env", "SHARED", "show"])
+                .current_dir(tmp_dir.clone())
+                .fails()
+                .and()
+                .stderr()
+                .contains("--env must be KEY=VALUE, not SHARED")
+                .unwrap();
+        });
+    }
+
+    #[test]
+    fn missing_env_file_fails() {
+        with_test_dir(|tmp_dir| {
+            let file_path = tmp_dir.clone().join("inc.yaml");
+            copy_resource("sample13.yaml", file_path);
+
+            create_assert()
+                .with_args(&["exec", "show"])
+                .current_dir(tmp_dir.clone())
+                .fails()
+                .and()
+                .stderr()
+                .contains("Unable to read env file")
+                .unwrap();
+        });
+    }
+
+    #[test]
+    fn steps_are_skipped_when_they_dont_match() {
+        with_test_dir(|tmp_dir| {
+            let file_path = tmp_dir.clone().join("inc.yaml");
+            copy_resource("sample14.yaml", file_path);
+            fs::write(tmp_dir.join("marker.txt"), "yes\n").unwrap();
+
+            create_assert()
+                .with_args(&["exec", "conditional"])
+                .current_dir(tmp_dir.clone())
+                .succeeds()
+                .and()
+                .stdout()
+                .contains("On Linux")
+                .stdout()
+                .contains("Has Marker")
+                .stdout()
+                .contains("Skipping `echo \"On Windows\"`, `when` didn't match")
+                .stdout()
+                .contains("Skipping `echo \"No Marker\"`")
+                .stdout()
+                .contains("Skipping `echo \"Is Set\"`")
+                .unwrap();
+
+            create_assert()
+                .with_args(&["exec", "conditional"])
+                .with_env(Environment::inherit().insert("INC_TEST_FLAG", "on"))
+                .current_dir(tmp_dir.clone())
+                .succeeds()
+                .and()
+                .stdout()
+                .contains("\nIs Set")
+                .unwrap();
+        });
+    }
+
+    #[test]
+    fn skipped_commands_dont_fail_dependents() {
+        with_test_dir(|tmp_dir| {
+            let file_path = tmp_dir.clone().join("inc.yaml");
+            copy_resource("sample14.yaml", file_path);
+
+            create_assert()
+                .with_args(&["exec", "after-skipped"])
+                .current_dir(tmp_dir.clone())
+                .succeeds()
+                .and()
+                .stdout()
+                .contains("*** Skipping `skipped`, `when` didn't match")
+                .stdout()
+                .contains("Still Runs")
+                .stdout()
+                .doesnt_contain("Never Printed")
+                .unwrap();
+        });
+    }
+
+    #[test]
+    fn unchanged_commands_are_skipped() {
+        with_test_dir(|tmp_dir| {
+            let file_path = tmp_dir.clone().join("inc.yaml");
+            copy_resource("sample15.yaml", file_path);
+            fs::create_dir(tmp_dir.join("src")).unwrap();
+            fs::write(tmp_dir.join("src/a.txt"), "a\n").unwrap();
+
+            let generate = |expected: &str| {
+                create_assert()
+                    .with_args(&["exec", "generate"])
+                    .current_dir(tmp_dir.clone())
+                    .succeeds()
+                    .and()
+                    .stdout()
+                    .contains(expected)
+                    .unwrap();
+            };
+
+            generate("Generated");
+            generate("*** `generate` is up to date, skipping");
+
+            fs::write(tmp_dir.join("src/b.txt"), "b\n").unwrap();
+            generate("Generated");
+            generate("is up to date");
+
+            fs::remove_file(tmp_dir.join("out/all.txt")).unwrap();
+            generate("Generated");
+
+            create_assert()
+                .with_args(&["exec", "--force", "generate"])
+                .current_dir(tmp_dir.clone())
+                .succeeds()
+                .and()
+                .stdout()
+                .contains("Generated")
+                .unwrap();
+
+            assert_eq!(
+                fs::read_to_string(tmp_dir.join("out/all.txt")).unwrap(),
+                "a\nb\n"
+            );
+        });
+    }
+
+    #[test]
+    fn watch_runs_again_when_files_change() {
+        with_test_dir(|tmp_dir| {
+            let file_path = tmp_dir.clone().join("inc.yaml");
+            copy_resource("sample16.yaml", file_path);
+            fs::create_dir(tmp_dir.join("src")).unwrap();
+            fs::write(tmp_dir.join("src/input.txt"), "First Run\n").unwrap();
+
+            let mut child = Command::new(build_exec())
+                .args(&["exec", "--watch", "watched"])
+                .current_dir(tmp_dir.clone())
+                .stdout(Stdio::piped())
+                .spawn()
+                .unwrap();
+
+            sleep(Duration::from_secs(1));
+            fs::write(tmp_dir.join("src/input.txt"), "Second Run\n").unwrap();
+            sleep(Duration::from_secs(2));
+            fs::write(tmp_dir.join("src/ignored.md"), "Not Watched\n").unwrap();
+            sleep(Duration::from_secs(1));
+
+            child.kill().unwrap();
+            let mut output = String::new();
+            child.stdout.take().unwrap().read_to_string(&mut output).unwrap();
+            child.wait().unwrap();
+
+            assert_eq!(
+                output,
+                "First Run\n*** Finished, waiting for changes\n*** Files changed, running again\nSecond Run\n*** Finished, waiting for changes\n"
+            );
+        });
+    }
+
+    #[test]
+    fn control_c_stops_watching() {
+        with_test_dir(|tmp_dir| {
+            let file_path = tmp_dir.clone().join("inc.yaml");
+            copy_resource("sample16.yaml", file_path);
+            fs::create_dir(tmp_dir.join("src")).unwrap();
+            fs::write(tmp_dir.join("src/input.txt"), "First Run\n").unwrap();
+
+            let (code, output) = interrupt_every_second(&tmp_dir, &["exec", "--watch", "watched"], 1);
+            assert_eq!(code, Some(130));
+            assert_eq!(output, "First Run\n*** Finished, waiting for changes\n");
+
+            let started = Instant::now();
+            let (code, output) = interrupt_every_second(&tmp_dir, &["exec", "--watch", "slow"], 2);
+            assert_eq!(code, Some(130));
+            assert!(started.elapsed() < Duration::from_secs(5));
+            assert!(output.contains("Cancelled\n*** Cancelled, waiting for changes\n"), "{}", output);
+            assert!(!output.contains("Never Printed"));
+
+            // A run that doesn't stop on the first control-c
+            let started = Instant::now();
+            let (code, output) = interrupt_every_second(&tmp_dir, &["exec", "--watch", "stubborn"], 2);
+            assert_eq!(code, Some(130));
+            assert!(started.elapsed() < Duration::from_secs(4));
+            assert!(!output.contains("waiting for changes"), "{}", output);
+        });
+    }
+
+    #[test]
+    fn control_c_stops_parallel_steps() {
+        with_test_dir(|tmp_dir| {
+            let file_path = tmp_dir.clone().join("inc.yaml");
+            copy_resource("sample27.yaml", file_path);
+
+            let started = Instant::now();
+            let (code, output) = interrupt_every_second(&tmp_dir, &["exec", "-j", "2", "slow"], 1);
+
+            assert_eq!(code, Some(130));
+            assert!(started.elapsed() < Duration::from_secs(5));
+            assert!(output.contains("[slow:3] Cleaning up\n"));
+            assert!(!output.contains("] Never Printed\n"));
+        });
+    }
+
+    /**
+     * The exit code and output of a run that got `count` control-c's, a second apart.
+     */
+    fn interrupt_every_second(dir: &Path, args: &[&str], count: usize) -> (Option<i32>, String) {
+        // In it's own process group, so the SIGINT reaches inc like a control-c in a terminal
+        let mut child = Command::new(build_exec())
+            .args(args)
+            .current_dir(dir)
+            .stdout(Stdio::piped())
+            .process_group(0)
+            .spawn()
+            .unwrap();
+
+        for _ in 0..count {
+            sleep(Duration::from_secs(1));
+            let interrupted = Command::new("kill")
+                .args(&["-INT", "--", &format!("-{}", child.id())])
+                .status()
+                .unwrap();
+            assert!(interrupted.success());
+        }
+
+        let mut output = String::new();
+        child.stdout.take().unwrap().read_to_string(&mut output).unwrap();
+        return (child.wait().unwrap().code(), output);
+    }
+
+    #[test]
+    fn control_c_stops_retries() {
+        with_test_dir(|tmp_dir| {
+            let file_path = tmp_dir.clone().join("inc.yaml");
+            copy_resource("sample17.yaml", file_path);
+
+            let started = Instant::now();
+            let (code, output) = interrupt_every_second(&tmp_dir, &["exec", "interrupted"], 1);
+
+            assert_eq!(code, Some(130));
+            assert!(started.elapsed() < Duration::from_secs(3), "{:?}", started.elapsed());
+            assert!(output.contains("Cleaning up\n"), "{}", output);
+        });
+    }
+
+    #[test]
+    fn parallel_steps_get_no_input() {
+        with_test_dir(|tmp_dir| {
+            let file_path = tmp_dir.clone().join("inc.yaml");
+            copy_resource("sample27.yaml", file_path);
+
+            let output = run_with_open_input(&tmp_dir, &["exec", "-j", "2", "ask"]);
+            assert!(output.contains("[ask:1] No input\n"), "{}", output);
+        });
+    }
+
+    /**
+     * The output of a successful run, with an input that's kept open, so anything reading it
+     * would wait forever.
+     */
+    fn run_with_open_input(dir: &Path, args: &[&str]) -> String {
+        let mut child = Command::new(build_exec())
+            .args(args)
+            .current_dir(dir)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .unwrap();
+
+        let started = Instant::now();
+        while child.try_wait().unwrap().is_none() && started.elapsed() < Duration::from_secs(10) {
+            sleep(Duration::from_millis(100));
+        }
+        if child.try_wait().unwrap().is_none() {
+            child.kill().unwrap();
+            panic!("Waited for input");
+        }
+
+        let mut output = String::new();
+        child.stdout.take().unwrap().read_to_string(&mut output).unwrap();
+        assert!(child.wait().unwrap().success());
+        return output;
+    }
+
+    #[test]
+    fn steps_time_out() {
+        with_test_dir(|tmp_dir| {
+            let file_path = tmp_dir.clone().join("inc.yaml");
+            copy_resource("sample17.yaml", file_path);
+
+            create_assert()
+                .with_args(&["exec", "slow"])
+                .current_dir(tmp_dir.clone())
+                .fails_with(124)
+                .and()
+                .stderr()
+                .contains("`sleep 10` timed out after 1s")
+                .unwrap();
+        });
+    }
+
+    #[test]
+    fn failed_steps_are_retried() {
+        with_test_dir(|tmp_dir| {
+            let file_path = tmp_dir.clone().join("inc.yaml");
+            copy_resource("sample17.yaml", file_path);
+
+            create_assert()
+                .with_args(&["exec", "flaky"])
+                .current_dir(tmp_dir.clone())
+                .succeeds()
+                .and()
+                .stdout()
+                .contains("returned 1, retrying in 100ms (1 of 3)")
+                .stdout()
+                .contains("returned 1, retrying in 200ms (2 of 3)")
+                .stdout()
+                .doesnt_contain("(3 of 3)")
+                .unwrap();
+
+            create_assert()
+                .with_args(&["exec", "always-fails"])
+                .current_dir(tmp_dir.clone())
+                .fails_with(3)
+                .and()
+                .stdout()
+                .contains("`exit 3` returned 3, retrying in 10ms (2 of 2)")
+                .stderr()
+                .contains("Command: `exit 3` returned 3")
+                .unwrap();
+        });
+    }
+
+    #[test]
+    fn dry_run_shows_the_plan() {
+        with_test_dir(|tmp_dir| {
+            let file_path = tmp_dir.clone().join("inc.yaml");
+            copy_resource("sample18.yaml", file_path);
+
+            create_assert()
+                .with_args(&["exec", "--dry-run", "test", "--", "extra"])
+                .current_dir(tmp_dir.clone())
+                .succeeds()
+                .and()
+                .stdout()
+                .contains(format!(
+                    "1. `build` from {}/inc.yaml\n   1.1 `echo \"Never Printed\"`\n       run: sh -c 'echo \"Never Printed\"'\n       shell: sh\n",
+                    tmp_dir.to_str().unwrap()
+                ).as_str())
+                .stdout()
+                .contains("2. `test` from ")
+                .stdout()
+                .contains("   depends on: build\n   2.1 `cargo test all`\n       run: bash -c 'cargo test all'\n       shell: bash\n")
+                .stdout()
+                .contains(format!("       cwd: {}/sub\n", tmp_dir.to_str().unwrap()).as_str())
+                .stdout()
+                .contains("         +INC_TEST_LOG=debug\n")
+                .stdout()
+                .contains("         +INC_TEST_SHARED=shared\n")
+                .stdout()
+                .contains("   2.2 `ls -la extra`\n       run: ls -la extra\n       shell: none\n")
+                .stdout()
+                .doesnt_contain("\nNever Printed")
+                .unwrap();
+        });
+    }
+
+    #[test]
+    fn list_commands_as_json_and_yaml() {
+        with_test_dir(|tmp_dir| {
+            let file_path = tmp_dir.clone().join("inc.yaml");
+            copy_resource("sample18.yaml", file_path);
+
+            create_assert()
+                .with_args(&["exec", "--list-commands", "--format", "json"])
+                .current_dir(tmp_dir.clone())
+                .succeeds()
+                .and()
+                .stdout()
+                .contains(format!(
+                    "{{
+  \"commands\": [
+    {{
+      \"name\": \"build\",
+      \"description\": \"No Description Provided\",
+      \"defined_in\": \"{}/inc.yaml\",
+      \"depends_on\": [],
+      \"env\": {{
+        \"INC_TEST_SHARED\": \"shared\"
+      }},
+      \"params\": [],
+      \"steps\": [
+        {{
+          \"type\": \"command\",
+          \"command\": \"echo \\\"Never Printed\\\"\",
+          \"env\": {{}}
+        }}
+      ]
+    }},",
+                    tmp_dir.to_str().unwrap()
+                ).as_str())
+                .unwrap();
+
+            create_assert()
+                .with_args(&["exec", "--list-commands", "--format", "yaml"])
+                .current_dir(tmp_dir.clone())
+                .succeeds()
+                .and()
+                .stdout()
+                .contains(
+                    "      - type: argv
+        command: ls -la
+        env: {}",
+                ).unwrap();
+        });
+    }
+
+    #[test]
+    fn reports_are_written() {
+        with_test_dir(|tmp_dir| {
+            let file_path = tmp_dir.clone().join("inc.yaml");
+            copy_resource("sample19.yaml", file_path);
+
+            create_assert()
+                .with_args(&[
+                    "exec",
+                    "ci",
+                    "--report",
+                    "reports/inc.json",
+                    "--report-output",
+                ])
+                .current_dir(tmp_dir.clone())
+                .fails_with(2)
+                .unwrap();
+
+            let report = fs::read_to_string(tmp_dir.join("reports/inc.json")).unwrap();
+            assert!(report.contains("\"exit_code\": 2,"), "{}", report);
+            assert!(report.contains("\"output\": \"Building <inc> & friends\\n\""), "{}", report);
+            assert!(report.contains("\"status\": \"skipped\""), "{}", report);
+            assert!(report.contains("\"status\": \"failed\""), "{}", report);
+            assert!(report.contains("\"status\": \"not_run\""), "{}", report);
+
+            create_assert()
+                .with_args(&["exec", "ci", "--report", "inc.xml", "--report-output"])
+                .current_dir(tmp_dir.clone())
+                .fails_with(2)
+                .unwrap();
+
+            let report = fs::read_to_string(tmp_dir.join("inc.xml")).unwrap();
+            assert!(
+                report.contains("<testsuites name=\"inc exec\" tests=\"4\" failures=\"1\" skipped=\"2\""),
+                "{}",
+                report
+            );
+            assert!(
+                report.contains("<system-out>Building &lt;inc&gt; &amp; friends\n</system-out>"),
+                "{}",
+                report
+            );
+            assert!(report.contains("<failure message=\"`exit 2` returned 2\"/>"), "{}", report);
+            assert!(
+                report.contains("<skipped message=\"A dependency failed, or the run stopped\"/>"),
+                "{}",
+                report
+            );
+        });
+    }
+
+    #[test]
+    fn params_are_parsed_and_validated() {
+        with_test_dir(|tmp_dir| {
+            let file_path = tmp_dir.clone().join("inc.yaml");
+            copy_resource("sample20.yaml", file_path);
+
+            create_assert()
+                .with_args(&["exec", "deploy", "--", "--tag", "v1", "--dry-run"])
+                .current_dir(tmp_dir.clone())
+                .succeeds()
+                .and()
+                .stdout()
+                .is("Deploying v1 to staging-v1, 2 replicas, dry run true")
+                .unwrap();
+
+            create_assert()
+                .with_args(&["exec", "deploy", "--", "--tag", "v1", "--env", "prod"])
+                .current_dir(tmp_dir.clone())
+                .fails_with(2)
+                .and()
+                .stderr()
+                .contains("'prod' isn't a valid value for '--env <env>'")
+                .unwrap();
+
+            create_assert()
+                .with_args(&["exec", "deploy", "--", "--tag", "v1", "--replicas", "many"])
+                .current_dir(tmp_dir.clone())
+                .fails_with(2)
+                .and()
+                .stderr()
+                .contains("`many` isn't a whole number")
+                .unwrap();
+
+            create_assert()
+                .with_args(&["exec", "release"])
+                .current_dir(tmp_dir.clone())
+                .fails_with(2)
+                .and()
+                .stderr()
+                .contains(
+                    "Only the last command gets params, so deploy uses it's defaults.
+error: The following required arguments were not provided:
+    --tag <tag>",
+                ).unwrap();
+        });
+    }
+
+    #[test]
+    fn params_are_shown_in_help() {
+        with_test_dir(|tmp_dir| {
+            let file_path = tmp_dir.clone().join("inc.yaml");
+            copy_resource("sample20.yaml", file_path);
+
+            create_assert()
+                .with_args(&["exec", "help", "deploy"])
+                .current_dir(tmp_dir.clone())
+                .succeeds()
+                .and()
+                .stdout()
+                .contains(
+                    "Deploy the app
+
+USAGE:
+    inc exec deploy -- [FLAGS] [OPTIONS] --tag <tag>",
+                ).and()
+                .stdout()
+                .contains("The steps get the params as environment variables, like INC_PARAM_ENV.")
+                .unwrap();
+
+            create_assert()
+                .with_args(&["exec", "--list-commands"])
+                .current_dir(tmp_dir.clone())
+                .succeeds()
+                .and()
+                .stdout()
+                .contains(
+                    "   params:
+     - --env <staging|production> (default: staging) Where to deploy
+     - --replicas <int> (default: 2)
+     - --dry-run
+     - --tag <string> (required) The image to deploy",
+                ).unwrap();
+        });
+    }
+
+    #[test]
+    fn matrix_runs_every_combination() {
+        with_test_dir(|tmp_dir| {
+            let file_path = tmp_dir.clone().join("inc.yaml");
+            copy_resource("sample21.yaml", file_path);
+
+            create_assert()
+                .with_args(&["exec", "-j", "2", "release"])
+                .current_dir(tmp_dir.clone())
+                .succeeds()
+                .and()
+                .stdout()
+                .contains(
+                    "*** Running `build[target=linux]`
+[build[target=linux]:1] ** Executing `echo \"Building linux\"`
+[build[target=linux]:1] Building linux
+[build[target=linux]:2] ** Executing `echo \"Packaging $INC_MATRIX_TARGET\"`
+[build[target=linux]:2] Packaging linux
+*** Running `build[target=windows]`",
+                ).and()
+                .stdout()
+                .contains("[build[target=wasm]:2] Packaging wasm")
+                .and()
+                .stdout()
+                .doesnt_contain("mac")
+                .and()
+                .stdout()
+                .contains("Released")
+                .unwrap();
+        });
+    }
+
+    #[test]
+    fn parallel_matrix_runs_at_the_same_time() {
+        with_test_dir(|tmp_dir| {
+            let file_path = tmp_dir.clone().join("inc.yaml");
+            copy_resource("sample21.yaml", file_path);
+
+            create_assert()
+                .with_args(&["exec", "-j", "2", "checks"])
+                .current_dir(tmp_dir.clone())
+                .succeeds()
+                .and()
+                .stdout()
+                .contains(
+                    "*** Running `checks[check=fmt]`
+*** Running `checks[check=lint]`",
+                ).unwrap();
+        });
+    }
+
+    #[test]
+    fn failed_steps_show_the_end_of_their_log() {
+        with_test_dir(|tmp_dir| {
+            let file_path = tmp_dir.clone().join("inc.yaml");
+            copy_resource("sample22.yaml", file_path);
+
+            create_assert()
+                .with_args(&["exec", "test"])
+                .current_dir(tmp_dir.clone())
+                .fails_with(3)
+                .and()
+                .stdout()
+                .contains("line 30")
+                .and()
+                .stderr()
+                .contains("Last 20 lines of the output:\n    line 11\n")
+                .and()
+                .stderr()
+                .doesnt_contain("line 10\n")
+                .and()
+                .stderr()
+                .contains("The full output is in")
+                .unwrap();
+
+            let runs: Vec<_> = fs::read_dir(tmp_dir.join("logs")).unwrap().collect();
+            assert_eq!(1, runs.len());
+            let run_dir = runs[0].as_ref().unwrap().path();
+            let first_step = fs::read_to_string(run_dir.join("test.1.log")).unwrap();
+            assert_eq!("Testing\n", first_step);
+            let second_step = fs::read_to_string(run_dir.join("test.2.log")).unwrap();
+            assert!(second_step.starts_with("line 1\n"), "{}", second_step);
+            assert!(second_step.ends_with("line 30\n"), "{}", second_step);
+
+            create_assert()
+                .with_args(&["exec", "test", "--log-dir", "other-logs"])
+                .current_dir(tmp_dir.clone())
+                .fails_with(3)
+                .unwrap();
+
+            assert_eq!(1, fs::read_dir(tmp_dir.join("other-logs")).unwrap().count());
+            assert_eq!(1, fs::read_dir(tmp_dir.join("logs")).unwrap().count());
+        });
+    }
+
+    #[test]
+    fn secrets_are_masked() {
+        with_test_dir(|tmp_dir| {
+            let file_path = tmp_dir.clone().join("inc.yaml");
+            copy_resource("sample23.yaml", file_path);
+            fs::write(tmp_dir.join("secrets.env"), "DB_PASSWORD=hunter2\n").unwrap();
+
+            create_assert()
+                .with_args(&["-vvv", "exec", "show", "--log-dir", "logs"])
+                .with_env(Environment::inherit().insert("INC_TEST_TOKEN", "s3cr3t-value"))
+                .current_dir(tmp_dir.clone())
+                .succeeds()
+                .and()
+                .stdout()
+                .contains("token is ***")
+                .and()
+                .stdout()
+                .contains("key is ***")
+                .and()
+                .stdout()
+                .doesnt_contain("k3y-value")
+                .and()
+                .stdout()
+                .contains("** Executing `echo \"Deploying with ***\"`")
+                .and()
+                .stdout()
+                .contains("\"NOT_SECRET\": \"visible\"")
+                .and()
+                .stdout()
+                .doesnt_contain("s3cr3t-value")
+                .and()
+                .stdout()
+                .doesnt_contain("hunter2")
+                .and()
+                .stderr()
+                .contains("password is ***")
+                .and()
+                .stderr()
+                .doesnt_contain("hunter2")
+                .unwrap();
+
+            let run_dir = fs::read_dir(tmp_dir.join("logs")).unwrap().next().unwrap();
+            let log = fs::read_to_string(run_dir.unwrap().path().join("show.2.log")).unwrap();
+            assert_eq!("password is ***\n", log);
+
+            create_assert()
+                .with_args(&["exec", "--dry-run", "show"])
+                .with_env(Environment::inherit().insert("INC_TEST_TOKEN", "s3cr3t-value"))
+                .current_dir(tmp_dir.clone())
+                .succeeds()
+                .and()
+                .stdout()
+                .contains("         +DB_PASSWORD=***\n")
+                .and()
+                .stdout()
+                .contains("run: sh -c 'echo \"Deploying with ***\"'")
+                .and()
+                .stdout()
+                .doesnt_contain("hunter2")
+                .unwrap();
+
+            create_assert()
+                .with_args(&["exec", "--list-commands", "--format", "json"])
+                .current_dir(tmp_dir.clone())
+                .succeeds()
+                .and()
+                .stdout()
+                .contains("\"DB_PASSWORD\": \"***\"")
+                .and()
+                .stdout()
+                .contains("\"API_KEY\": \"***\"")
+                .unwrap();
+        });
+    }
+
+    #[test]
+    fn finally_steps_always_run() {
+        with_test_dir(|tmp_dir| {
+            let file_path = tmp_dir.clone().join("inc.yaml");
+            copy_resource("sample24.yaml", file_path);
+
+            create_assert()
+                .with_args(&["exec", "test"])
+                .current_dir(tmp_dir.clone())
+                .fails_with(3)
+                .and()
+                .stdout()
+                .contains("** Executing `exit 3`\n*** Running the `finally` steps of `test`\n** Executing `echo \"Stopping the stack\"`\nStopping the stack\n")
+                .and()
+                .stdout()
+                .contains("Removing the database")
+                .and()
+                .stdout()
+                .doesnt_contain("Never Printed")
+                .and()
+                .stderr()
+                .contains("Command: `exit 3` returned 3")
+                .and()
+                .stderr()
+                .contains("Finally: `exit 5` returned 5")
+                .unwrap();
+
+            create_assert()
+                .with_args(&["exec", "clean"])
+                .current_dir(tmp_dir.clone())
+                .succeeds()
+                .and()
+                .stdout()
+                .contains(
+                    "Building
+*** Running the `finally` steps of `build`
+** Executing `echo \"Build cleaned up\"`
+Build cleaned up
+*** Running `clean`
+** Executing `echo \"Clean run\"`
+Clean run
+*** Running the `finally` steps of `clean`
+** Executing `echo \"Cleaning up\"`
+Cleaning up
+",
+                ).unwrap();
+
+            create_assert()
+                .with_args(&["exec", "cleanup-fails"])
+                .current_dir(tmp_dir.clone())
+                .fails_with(4)
+                .and()
+                .stdout()
+                .contains("Worked")
+                .and()
+                .stderr()
+                .contains("Finally: `exit 4` returned 4")
+                .unwrap();
+
+            create_assert()
+                .with_args(&["exec", "--dry-run", "test"])
+                .current_dir(tmp_dir.clone())
+                .succeeds()
+                .and()
+                .stdout()
+                .contains("   1.4 `echo \"Stopping the stack\"` (finally)\n")
+                .unwrap();
+        });
+    }
+    #[test]
+    fn services_run_while_the_command_runs() {
+        with_test_dir(|tmp_dir| {
+            let file_path = tmp_dir.clone().join("inc.yaml");
+            copy_resource("sample25.yaml", file_path);
+
+            create_assert()
+                .with_args(&["exec", "test"])
+                .current_dir(tmp_dir.clone())
+                .succeeds()
+                .and()
+                .stdout()
+                .contains("*** Starting `server`\n[server] Listening\n*** `server` is ready\n*** Starting `marker`\n*** `marker` is ready\n")
+                .and()
+                .stdout()
+                .contains("Tests done\n*** Stopping `marker`\n*** Stopping `server`\n")
+                .and()
+                .stdout()
+                .contains("[server] Shutting down")
+                .unwrap();
+
+            create_assert()
+                .with_args(&["exec", "crashes"])
+                .current_dir(tmp_dir.clone())
+                .fails_with(1)
+                .and()
+                .stdout()
+                .doesnt_contain("Never Printed")
+                .and()
+                .stderr()
+                .contains("`server` exited with 7 before it was ready")
+                .unwrap();
+
+            create_assert()
+                .with_args(&["exec", "slow"])
+                .current_dir(tmp_dir.clone())
+                .fails_with(1)
+                .and()
+                .stdout()
+                .doesnt_contain("Never Printed")
+                .and()
+                .stderr()
+                .contains("Timed out after 1s waiting for `server` to be ready")
+                .unwrap();
+
+            create_assert()
+                .with_args(&["exec", "--dry-run", "test"])
+                .current_dir(tmp_dir.clone())
+                .succeeds()
+                .and()
+                .stdout()
+                .contains("   service: `marker`, waiting for `test -f started.marker` to succeed\n")
+                .unwrap();
+
+            let output = run_with_open_input(&tmp_dir, &["exec", "ask"]);
+            assert!(output.contains("[server] No input\n*** `server` is ready\nServer is ready\n"), "{}", output);
+        });
+    }
+
+    #[test]
+    fn wait_for_steps() {
+        with_test_dir(|tmp_dir| {
+            let file_path = tmp_dir.clone().join("inc.yaml");
+            copy_resource("sample26.yaml", file_path);
+
+            create_assert()
+                .with_args(&["exec", "test"])
+                .current_dir(tmp_dir.clone())
+                .succeeds()
+                .and()
+                .stdout()
+                .contains("** Executing `wait for file target.ready`\n** Executing `wait for command test -f target.ready`\n** Executing `echo \"Ready\"`\nReady\n")
+                .unwrap();
+
+            create_assert()
+                .with_args(&["exec", "port"])
+                .current_dir(tmp_dir.clone())
+                .fails_with(124)
+                .and()
+                .stdout()
+                .doesnt_contain("Never Printed")
+                .and()
+                .stderr()
+                .contains("Timed out after 1s waiting for localhost:1 to accept connections")
+                .unwrap();
+
+            create_assert()
+                .with_args(&["exec", "invalid"])
+                .current_dir(tmp_dir.clone())
+                .fails_with(2)
+                .and()
+                .stderr()
+                .contains("wait_for needs exactly one of tcp, file, http or command")
+                .unwrap();
+
+            create_assert()
+                .with_args(&["exec", "--dry-run", "port"])
+                .current_dir(tmp_dir.clone())
+                .succeeds()
+                .and()
+                .stdout()
+                .contains("   1.1 `wait for tcp 1`\n       wait: localhost:1 to accept connections, for up to 1s\n")
+                .unwrap();
+        });
+    }
+
+    #[test]
+    fn steps_without_a_command_fail() {
+        with_test_dir(|tmp_dir| {
+            let file_path = tmp_dir.clone().join("inc.yaml");
+            copy_resource("sample28.yaml", file_path);
+
+            create_assert()
+                .with_args(&["exec", "typo"])
+                .current_dir(tmp_dir.clone())
+                .fails_with(2)
+                .and()
+                .stderr()
+                .contains("Unable to run a step in typo: a step needs a command, a script or argv")
+                .unwrap();
+
+            create_assert()
+                .with_args(&["exec", "--dry-run", "env-only"])
+                .current_dir(tmp_dir.clone())
+                .fails_with(2)
+                .and()
+                .stderr()
+                .contains("Unable to run a step in env-only: a step needs a command, a script or argv")
+                .unwrap();
+        });
+    }
 }
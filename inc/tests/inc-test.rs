@@ -60,4 +60,21 @@ SUBCOMMANDS:
   - list",
             ).unwrap();
     }
+
+    #[test]
+    fn calling_inc_with_list_as_json() {
+        assert_cli::Assert::main_binary()
+            .with_args(&["list", "--format", "json"])
+            .succeeds()
+            .and()
+            .stdout()
+            .contains(
+                "{
+  \"commands\": [
+    {
+      \"name\": \"checkout\",
+      \"source\": \"builtin\"
+    },",
+            ).unwrap();
+    }
 }